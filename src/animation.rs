@@ -0,0 +1,288 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::{
+    camera::CameraBuilder,
+    geometry::Transform,
+    vec3::{Point3, Vec3},
+};
+
+/// Values that can be blended between keyframes.
+pub trait Interpolate:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self>
+{
+}
+
+impl<T> Interpolate for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {}
+
+/// How a track moves from one keyframe to the next.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum Interpolation {
+    /// In a straight line, at a constant speed.
+    #[default]
+    Linear,
+    /// Along a Catmull-Rom spline, which passes smoothly through every keyframe.
+    CatmullRom,
+    /// Along a cubic Bezier curve, with each keyframe's handles as its control points.  Keyframes
+    /// without handles are eased into and out of.
+    Bezier,
+}
+
+/// A value at a point in time.
+#[derive(Debug, Copy, Clone)]
+pub struct Keyframe<T> {
+    /// The frame the value is reached at.
+    pub frame: f32,
+    pub value: T,
+    /// The Bezier control points before and after the keyframe, relative to its value.
+    pub handles: (T, T),
+}
+
+impl<T: Interpolate> Keyframe<T> {
+    pub fn new(frame: f32, value: T) -> Self {
+        Self {
+            frame,
+            value,
+            handles: (value * 0.0, value * 0.0),
+        }
+    }
+
+    /// Sets the Bezier control points before and after the keyframe, relative to its value.
+    pub fn with_handles(mut self, before: T, after: T) -> Self {
+        self.handles = (before, after);
+        self
+    }
+}
+
+/// A value that changes over time, given by its keyframes.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+    interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self {
+            keyframes: Vec::new(),
+            interpolation,
+        }
+    }
+
+    /// Adds `keyframe` to the track, in order of frame.
+    pub fn with_keyframe(mut self, keyframe: Keyframe<T>) -> Self {
+        let index = self
+            .keyframes
+            .partition_point(|k| k.frame <= keyframe.frame);
+        self.keyframes.insert(index, keyframe);
+        self
+    }
+
+    /// Adds a keyframe without handles, reaching `value` at `frame`.
+    pub fn with_key(self, frame: f32, value: T) -> Self {
+        self.with_keyframe(Keyframe::new(frame, value))
+    }
+
+    /// The value at `frame`, which holds still before the first keyframe and after the last.
+    /// Returns `None` for tracks without keyframes.
+    pub fn at(&self, frame: f32) -> Option<T> {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|k| k.frame <= frame);
+        if next == 0 || next == keys.len() {
+            return keys.get(next.saturating_sub(1)).map(|k| k.value);
+        }
+
+        let (k0, k1) = (&keys[next - 1], &keys[next]);
+        let length = k1.frame - k0.frame;
+        let t = (frame - k0.frame) / length;
+
+        Some(match self.interpolation {
+            Interpolation::Linear => k0.value + (k1.value - k0.value) * t,
+            Interpolation::CatmullRom => {
+                // the tangent at each keyframe is the slope between its neighbours, scaled to
+                // the length of the segment.
+                let tangent = |k: usize| {
+                    let (before, after) = (
+                        &keys[k.saturating_sub(1)],
+                        &keys[(k + 1).min(keys.len() - 1)],
+                    );
+                    (after.value - before.value) * (length / (after.frame - before.frame))
+                };
+                let (m0, m1) = (tangent(next - 1), tangent(next));
+
+                let (t2, t3) = (t * t, t * t * t);
+                k0.value * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + m0 * (t3 - 2.0 * t2 + t)
+                    + k1.value * (3.0 * t2 - 2.0 * t3)
+                    + m1 * (t3 - t2)
+            }
+            Interpolation::Bezier => {
+                let p1 = k0.value + k0.handles.1;
+                let p2 = k1.value + k1.handles.0;
+
+                let s = 1.0 - t;
+                k0.value * (s * s * s)
+                    + p1 * (3.0 * s * s * t)
+                    + p2 * (3.0 * s * t * t)
+                    + k1.value * (t * t * t)
+            }
+        })
+    }
+}
+
+/// Tracks for the camera settings that change over an animation.  Settings without a track are
+/// left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct CameraAnimation {
+    pub look_from: Option<Track<Point3>>,
+    pub look_at: Option<Track<Point3>>,
+    /// The vertical field of view, in degrees.
+    pub vfov: Option<Track<f32>>,
+    pub focus_dist: Option<Track<f32>>,
+}
+
+impl CameraAnimation {
+    pub fn with_look_from(mut self, track: Track<Point3>) -> Self {
+        self.look_from = Some(track);
+        self
+    }
+
+    pub fn with_look_at(mut self, track: Track<Point3>) -> Self {
+        self.look_at = Some(track);
+        self
+    }
+
+    pub fn with_vfov(mut self, track: Track<f32>) -> Self {
+        self.vfov = Some(track);
+        self
+    }
+
+    pub fn with_focus_dist(mut self, track: Track<f32>) -> Self {
+        self.focus_dist = Some(track);
+        self
+    }
+
+    /// Sets the animated settings of `camera` to their values at `frame`.  An animated focus
+    /// distance takes the place of any point the camera was focused on.
+    pub fn apply(&self, mut camera: CameraBuilder, frame: f32) -> CameraBuilder {
+        if let Some(look_from) = value_at(&self.look_from, frame) {
+            camera = camera.look_from(look_from);
+        }
+        if let Some(look_at) = value_at(&self.look_at, frame) {
+            camera = camera.look_at(look_at);
+        }
+        if let Some(vfov) = value_at(&self.vfov, frame) {
+            camera = camera.with_vertical_field_of_view(vfov);
+        }
+        if let Some(focus_dist) = value_at(&self.focus_dist, frame) {
+            camera = camera.with_focus_dist(focus_dist);
+        }
+        camera
+    }
+}
+
+/// Tracks for the parts of an object's transform that change over an animation.  Parts without
+/// a track are left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct TransformAnimation {
+    pub translation: Option<Track<Vec3>>,
+    /// Angles in degrees about the x, y and z axes.
+    pub rotation: Option<Track<Vec3>>,
+    pub scale: Option<Track<f32>>,
+}
+
+impl TransformAnimation {
+    pub fn with_translation(mut self, track: Track<Vec3>) -> Self {
+        self.translation = Some(track);
+        self
+    }
+
+    pub fn with_rotation(mut self, track: Track<Vec3>) -> Self {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn with_scale(mut self, track: Track<f32>) -> Self {
+        self.scale = Some(track);
+        self
+    }
+
+    /// The transform at `frame`.
+    pub fn at(&self, frame: f32) -> Transform {
+        let mut transform = Transform::default();
+        if let Some(translation) = value_at(&self.translation, frame) {
+            transform.translation = translation;
+        }
+        if let Some(rotation) = value_at(&self.rotation, frame) {
+            transform.rotation = rotation;
+        }
+        if let Some(scale) = value_at(&self.scale, frame) {
+            transform.scale = scale;
+        }
+        transform
+    }
+}
+
+/// The value of `track` at `frame`, if there's a track with keyframes.
+fn value_at<T: Interpolate>(track: &Option<Track<T>>, frame: f32) -> Option<T> {
+    track.as_ref().and_then(|track| track.at(frame))
+}
+
+/// Settings for circling the camera around the point it looks at, so that something can be seen
+/// from all sides.
+#[derive(Debug, Copy, Clone)]
+pub struct Turntable {
+    /// How many frames a full turn takes.
+    pub frames: u32,
+    /// How many degrees the camera rises above and sinks below where it starts over the turn.
+    pub elevation_sweep: f32,
+}
+
+impl Turntable {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames: frames.max(1),
+            elevation_sweep: 0.0,
+        }
+    }
+
+    pub fn with_elevation_sweep(mut self, degrees: f32) -> Self {
+        self.elevation_sweep = degrees;
+        self
+    }
+
+    /// Moves `camera` to where it is at `frame` of the turn, starting from where it is at frame
+    /// 0.  The camera turns about its up direction, keeping its distance from the point it
+    /// looks at.
+    pub fn apply(&self, camera: CameraBuilder, frame: u32) -> CameraBuilder {
+        // the same defaults the camera falls back on.
+        let look_from = camera.look_from.unwrap_or(Point3::new(0.0, 0.0, -1.0));
+        let look_at = camera.look_at.unwrap_or_default();
+        let up = camera.up.unwrap_or(Vec3::new(0.0, 1.0, 0.0)).normalize();
+
+        let offset = look_from - look_at;
+        let distance = offset.len();
+        let height = offset.dot(&up);
+        let across = offset - height * up;
+        // looking straight down the up direction, turn about any direction across it.
+        let across = if across.len_squared() > 1e-8 * distance * distance {
+            across.normalize()
+        } else {
+            let axis = if up.x().abs() < 0.9 {
+                Vec3::new(1.0, 0.0, 0.0)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+            up.cross(&axis).normalize()
+        };
+
+        let angle = std::f32::consts::TAU * frame as f32 / self.frames as f32;
+        let across = angle.cos() * across + angle.sin() * up.cross(&across);
+
+        // the elevation rises and falls back over the turn, so the sequence loops seamlessly.
+        let elevation = ((height / distance).clamp(-1.0, 1.0).asin()
+            + self.elevation_sweep.to_radians() * angle.sin())
+        .clamp(-89f32.to_radians(), 89f32.to_radians());
+
+        camera.look_from(look_at + distance * (elevation.cos() * across + elevation.sin() * up))
+    }
+}
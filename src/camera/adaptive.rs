@@ -0,0 +1,103 @@
+use std::io::Write;
+
+use super::film::Pixel;
+
+/// Settings for adaptive sampling, which takes samples for every pixel in passes, and stops
+/// sampling each pixel once the error in its estimate looks small enough.  Flat areas then get
+/// few samples, leaving more time for the noisy ones.
+#[derive(Debug, Copy, Clone)]
+pub struct AdaptiveSampling {
+    /// How many samples to take for every pixel before judging its error.  This is also how many
+    /// samples each later pass takes.
+    pub min_samples_per_pixel: u32,
+    /// How many samples to take for a pixel at most, however noisy it still is.
+    pub max_samples_per_pixel: u32,
+    /// The estimated error in a pixel's displayed brightness, from `0` to `1`, below which it
+    /// needs no more samples.
+    pub max_error: f32,
+}
+
+impl AdaptiveSampling {
+    /// Creates adaptive sampling settings that take between `min_samples_per_pixel` and
+    /// `max_samples_per_pixel` samples for each pixel, until the estimated error falls below
+    /// half a step of an 8 bit image.
+    pub fn new(min_samples_per_pixel: u32, max_samples_per_pixel: u32) -> Self {
+        let min_samples_per_pixel = min_samples_per_pixel.max(2);
+        Self {
+            min_samples_per_pixel,
+            max_samples_per_pixel: max_samples_per_pixel.max(min_samples_per_pixel),
+            max_error: 0.5 / 255.0,
+        }
+    }
+
+    pub fn with_max_error(mut self, max_error: f32) -> Self {
+        self.max_error = max_error;
+        self
+    }
+
+    /// Whether `pixel`, scaled by `exposure` when displayed, needs no more samples.
+    pub(super) fn is_converged(&self, pixel: &Pixel, exposure: f32) -> bool {
+        if pixel.samples() >= self.max_samples_per_pixel {
+            return true;
+        }
+        if pixel.samples() < self.min_samples_per_pixel {
+            return false;
+        }
+
+        // images are encoded with the sRGB curve for display, under which a small error e in a
+        // luminance l turns into an error of about e times the curve's slope at l.  errors in
+        // dark pixels are the more visible for it.  the tone map is left out, since it mostly
+        // flattens the bright pixels, which already need the fewest samples.
+        let luminance = pixel.luminance() * exposure;
+        let error = pixel.standard_error() * exposure * srgb_slope(luminance);
+        error <= self.max_error
+    }
+}
+
+/// The slope of the sRGB transfer function at the linear value `linear`.
+fn srgb_slope(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        12.92
+    } else {
+        1.055 / 2.4 * linear.powf(2.4f32.recip() - 1.0)
+    }
+}
+
+/// Writes a PPM image of how many samples each pixel got, from dark blue for `min` through red to
+/// yellow for `max`.
+pub(super) fn write_heatmap<Output: Write>(
+    output: &mut Output,
+    width: u64,
+    height: u64,
+    samples: impl Iterator<Item = u32>,
+    min: u32,
+    max: u32,
+) -> std::io::Result<()> {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.2],
+        [0.2, 0.1, 0.7],
+        [0.8, 0.1, 0.3],
+        [1.0, 0.6, 0.0],
+        [1.0, 1.0, 0.6],
+    ];
+
+    write!(output, "P3\n{} {}\n255\n", width, height)?;
+
+    for count in samples {
+        let t = if max > min {
+            (count.saturating_sub(min) as f32 / (max - min) as f32).min(1.0)
+        } else {
+            1.0
+        };
+        let position = t * (STOPS.len() - 1) as f32;
+        let stop = (position as usize).min(STOPS.len() - 2);
+        let fraction = position - stop as f32;
+        let [r, g, b] = [0, 1, 2].map(|c| {
+            let value = STOPS[stop][c] + fraction * (STOPS[stop + 1][c] - STOPS[stop][c]);
+            (255.0 * value).round() as u8
+        });
+        writeln!(output, "{} {} {}", r, g, b)?;
+    }
+
+    Ok(())
+}
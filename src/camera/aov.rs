@@ -0,0 +1,488 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Result, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use super::{checkpoint, film::Pixel, tone_map::Display};
+use crate::{
+    geometry::HitRecord,
+    material::Material,
+    ray::Ray,
+    util::suffixed_path,
+    vec3::{Color, Point3, Vec3},
+};
+
+/// A buffer of something other than the color each pixel sees, for compositing.  Everything
+/// about surfaces is about the first one a pixel's camera rays hit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aov {
+    /// The color of the surface, as it would look lit evenly from every side.
+    Albedo,
+    /// The surface's normal in world space, facing the camera.
+    Normal,
+    /// How far the surface is from the camera, or 0 where rays escape the scene.
+    Depth,
+    /// Where the surface is in world space.
+    Position,
+    /// Which of the world's objects the surface belongs to, counting from 1, or 0 for none.
+    ObjectId,
+    /// A number identifying the material of the surface, the same for materials that are alike,
+    /// or 0 for none.
+    MaterialId,
+    /// Light reflected by the surface straight from a light or the background.
+    Direct,
+    /// Light reflected by the surface after bouncing off or through others on the way.
+    Indirect,
+    /// Light seen without it reflecting off anything, which is just the background.
+    Emission,
+    /// The direct light from each light in the scene, in a buffer of its own.
+    Lights,
+}
+
+impl Aov {
+    /// The name of the buffer, used in file and layer names.
+    fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Emission => "emission",
+            Aov::Lights => "light",
+        }
+    }
+
+    /// What sort of values the buffer holds.
+    fn kind(&self) -> Kind {
+        match self {
+            Aov::Albedo => Kind::Color,
+            Aov::Normal => Kind::Direction,
+            Aov::Depth => Kind::Distance,
+            Aov::Position => Kind::Position,
+            Aov::ObjectId | Aov::MaterialId => Kind::Id,
+            Aov::Direct | Aov::Indirect | Aov::Emission | Aov::Lights => Kind::Light,
+        }
+    }
+}
+
+/// What sort of values a buffer holds, which decides how they're averaged and shown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    /// Light reaching the camera, which the exposure applies to.
+    Light,
+    Color,
+    /// A unit vector.
+    Direction,
+    Distance,
+    Position,
+    /// A whole number, which can't be averaged.
+    Id,
+}
+
+impl Kind {
+    /// The names of the channels of a buffer of this kind.
+    fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Kind::Light | Kind::Color => &["R", "G", "B"],
+            Kind::Direction | Kind::Position => &["X", "Y", "Z"],
+            Kind::Distance => &["Z"],
+            Kind::Id => &["id"],
+        }
+    }
+}
+
+/// How AOVs are written.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AovFormat {
+    /// A PPM image for each buffer, named after the path with the buffer's name added, e.g.
+    /// `out-albedo.ppm`.  Values that aren't colors are mapped to colors that show them.
+    #[default]
+    Ppm,
+    /// A single uncompressed OpenEXR file holding the image and a layer for each buffer, with
+    /// their values as they are.
+    Exr,
+}
+
+/// Settings for the buffers rendered alongside the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aovs {
+    pub buffers: Vec<Aov>,
+    pub format: AovFormat,
+    pub path: PathBuf,
+}
+
+impl Aovs {
+    pub fn new(buffers: Vec<Aov>, path: PathBuf) -> Self {
+        Self {
+            buffers,
+            format: AovFormat::default(),
+            path,
+        }
+    }
+
+    pub fn with_format(mut self, format: AovFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Writes the `layers` asked for, of a `width` by `height` image, to `path` rather than the
+    /// path in the settings.  The EXR holds the image's `colors` too, while PPMs of light are
+    /// shown through `display`.
+    pub(super) fn write(
+        &self,
+        path: &Path,
+        width: u64,
+        height: u64,
+        layers: &[Layer],
+        colors: &[Color],
+        display: &Display,
+    ) -> Result<()> {
+        let layers = layers
+            .iter()
+            .filter(|layer| self.buffers.contains(&layer.buffer));
+        match self.format {
+            AovFormat::Ppm => {
+                for layer in layers {
+                    let mut output =
+                        BufWriter::new(File::create(suffixed_path(path, &layer.name))?);
+                    layer.write_ppm(&mut output, width, height, display)?;
+                    output.flush()?;
+                }
+            }
+            AovFormat::Exr => {
+                let mut channels: Vec<(String, Vec<f32>)> = ["R", "G", "B"]
+                    .iter()
+                    .enumerate()
+                    .map(|(c, name)| {
+                        let values = colors.iter().map(|color| color[c]).collect();
+                        (name.to_string(), values)
+                    })
+                    .collect();
+                for layer in layers {
+                    let names = layer.buffer.kind().channels();
+                    for (c, name) in names.iter().enumerate() {
+                        let values = layer.values.iter().skip(c).step_by(names.len()).copied();
+                        channels.push((format!("{}.{name}", layer.name), values.collect()));
+                    }
+                }
+
+                let mut output = BufWriter::new(File::create(path)?);
+                write_exr(&mut output, width, height, &channels)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds what a sample saw to `sums`, a pixel's sums of the values of each of `buffers` in order.
+/// The sample's light is already weighted by the filter, and everything else but IDs is weighted
+/// by `filter_weight` here.  IDs are those the pixel's `first` sample saw.
+pub(super) fn add_sample(
+    buffers: &[Aov],
+    sums: &mut Vec<f32>,
+    sample: &AovSample,
+    filter_weight: f32,
+    first: bool,
+) {
+    if sums.is_empty() {
+        sums.resize(channels(buffers, sample.lights.len()), 0.0);
+    }
+
+    let mut sums = sums.iter_mut();
+    let mut add = |values: &[f32], kind: Kind| {
+        // the values come first, so that no sum is taken past the last of them.
+        for (&value, sum) in values.iter().zip(sums.by_ref()) {
+            match kind {
+                Kind::Light => *sum += value,
+                Kind::Id if first => *sum = value,
+                Kind::Id => {}
+                _ => *sum += filter_weight * value,
+            }
+        }
+    };
+
+    let surface = sample.surface.as_ref();
+    for buffer in buffers {
+        let kind = buffer.kind();
+        match buffer {
+            Aov::Albedo => add(&rgb(surface.map(|s| s.albedo)), kind),
+            Aov::Normal => add(&rgb(surface.map(|s| s.normal)), kind),
+            Aov::Depth => add(&[surface.map_or(0.0, |s| s.distance)], kind),
+            Aov::Position => add(&rgb(surface.map(|s| s.point)), kind),
+            Aov::ObjectId => add(&[surface.map_or(0, |s| s.object_id) as f32], kind),
+            Aov::MaterialId => {
+                // hashing the material takes a while, so only the sample that counts does.
+                let id = surface
+                    .filter(|_| first)
+                    .map_or(0, |s| material_id(s.material));
+                add(&[id as f32], kind)
+            }
+            Aov::Direct => add(&rgb(Some(sample.direct)), kind),
+            Aov::Indirect => add(&rgb(Some(sample.indirect)), kind),
+            Aov::Emission => add(&rgb(Some(sample.emission)), kind),
+            Aov::Lights => {
+                for &light in &sample.lights {
+                    add(&rgb(Some(light)), kind);
+                }
+            }
+        }
+    }
+}
+
+/// How many values each pixel keeps for `buffers`, in a scene with `lights` lights.
+fn channels(buffers: &[Aov], lights: usize) -> usize {
+    buffers
+        .iter()
+        .map(|buffer| match buffer {
+            Aov::Lights => lights * buffer.kind().channels().len(),
+            _ => buffer.kind().channels().len(),
+        })
+        .sum()
+}
+
+/// The values of each of `buffers` for `pixels`, in a scene with `lights` lights, with
+/// `exposure` applied to the light.  Each light gets a layer of its own.
+pub(super) fn layers(
+    buffers: &[Aov],
+    pixels: &[Pixel],
+    lights: usize,
+    exposure: f32,
+) -> Vec<Layer> {
+    let mut layers = Vec::new();
+    let mut offset = 0;
+    for &buffer in buffers {
+        let names = match buffer {
+            Aov::Lights => (1..=lights)
+                .map(|n| format!("{}{n}", buffer.name()))
+                .collect(),
+            _ => vec![buffer.name().to_string()],
+        };
+
+        let kind = buffer.kind();
+        let channels = kind.channels().len();
+        for name in names {
+            let values = pixels
+                .iter()
+                .flat_map(|pixel| {
+                    (offset..offset + channels).map(move |k| match kind {
+                        Kind::Id => pixel.aovs.get(k).copied().unwrap_or_default(),
+                        Kind::Light => pixel.aov(k) * exposure,
+                        _ => pixel.aov(k),
+                    })
+                })
+                .collect();
+            layers.push(Layer {
+                buffer,
+                name,
+                values,
+            });
+            offset += channels;
+        }
+    }
+    layers
+}
+
+/// What a single sample of a pixel saw, for the AOVs, with its light in whatever form the
+/// camera traces it in.
+#[derive(Debug, Default)]
+pub(super) struct AovSample<'a, S = Color> {
+    pub(super) surface: Option<Surface<'a>>,
+    pub(super) direct: S,
+    pub(super) indirect: S,
+    pub(super) emission: S,
+    /// The direct light from each light, in the order of the scene's lights.
+    pub(super) lights: Vec<S>,
+}
+
+impl<'a, S> AovSample<'a, S> {
+    /// Converts the sample's light with `f`.
+    pub(super) fn map<T>(self, f: impl Fn(S) -> T) -> AovSample<'a, T> {
+        AovSample {
+            surface: self.surface,
+            direct: f(self.direct),
+            indirect: f(self.indirect),
+            emission: f(self.emission),
+            lights: self.lights.into_iter().map(f).collect(),
+        }
+    }
+}
+
+/// The first surface a camera ray hit.
+#[derive(Debug)]
+pub(super) struct Surface<'a> {
+    albedo: Color,
+    normal: Vec3,
+    distance: f32,
+    point: Point3,
+    object_id: u32,
+    material: &'a dyn Material,
+}
+
+impl<'a> Surface<'a> {
+    pub(super) fn new(ray: &Ray, record: &HitRecord<'a>) -> Self {
+        Self {
+            albedo: record.material.albedo(),
+            normal: record.normal,
+            distance: record.t * ray.direction().len(),
+            point: record.point,
+            object_id: record.object_id,
+            material: record.material,
+        }
+    }
+}
+
+/// A number identifying `material` by its settings, small enough to be held exactly in a float
+/// and never 0.
+fn material_id(material: &dyn Material) -> u32 {
+    let hash = checkpoint::hash(&[format!("{material:?}").as_bytes()]);
+    (hash as u32 & 0xff_ffff).max(1)
+}
+
+fn rgb(value: Option<Vec3>) -> [f32; 3] {
+    value.map_or([0.0; 3], |v| [v.x(), v.y(), v.z()])
+}
+
+/// The values of one buffer for every pixel of an image.
+pub(super) struct Layer {
+    pub(super) buffer: Aov,
+    name: String,
+    /// The values of each of a pixel's channels, one pixel after another in row-major order.
+    pub(super) values: Vec<f32>,
+}
+
+impl Layer {
+    /// Writes the layer to `output` as a `width` by `height` PPM.  Light is shown through
+    /// `display` like the image, other colors as they are, directions and positions have their
+    /// components mapped to colors, distances are shown in shades of gray up to the furthest, and
+    /// each ID gets a color of its own.
+    fn write_ppm<Output: Write>(
+        &self,
+        output: &mut Output,
+        width: u64,
+        height: u64,
+        display: &Display,
+    ) -> Result<()> {
+        write!(output, "P3\n{} {}\n255\n", width, height)?;
+
+        let kind = self.buffer.kind();
+        let channels = kind.channels().len();
+        // the range of each channel, for mapping them to colors.
+        let mut ranges = vec![(f32::INFINITY, f32::NEG_INFINITY); channels];
+        for (c, &value) in self.values.iter().enumerate() {
+            let (min, max) = &mut ranges[c % channels];
+            (*min, *max) = (min.min(value), max.max(value));
+        }
+        let to_byte = |v: f32| (256.0 * v.clamp(0.0, 0.999)) as u8;
+
+        for values in self.values.chunks(channels) {
+            let [r, g, b] = match kind {
+                Kind::Light => display
+                    .apply_exposed(Color::new(values[0], values[1], values[2]))
+                    .to_rgb8(NonZeroU32::MIN),
+                Kind::Color => Color::new(values[0], values[1], values[2]).to_rgb8(NonZeroU32::MIN),
+                Kind::Direction => [0, 1, 2].map(|c| to_byte(0.5 * values[c] + 0.5)),
+                Kind::Position => [0, 1, 2].map(|c| {
+                    let (min, max) = ranges[c];
+                    to_byte((values[c] - min) / (max - min).max(f32::EPSILON))
+                }),
+                Kind::Distance => [to_byte(values[0] / ranges[0].1.max(f32::EPSILON)); 3],
+                Kind::Id => id_color(values[0] as u32),
+            };
+            writeln!(output, "{} {} {}", r, g, b)?;
+        }
+        Ok(())
+    }
+}
+
+/// A color for showing `id`, which tells neighbouring IDs apart.  0 is black.
+fn id_color(id: u32) -> [u8; 3] {
+    if id == 0 {
+        return [0; 3];
+    }
+    let hash = id.wrapping_mul(0x9e37_79b9);
+    let hash = hash ^ (hash >> 15);
+    // dark enough colors would be hard to tell from none.
+    [hash as u8, (hash >> 8) as u8, (hash >> 16) as u8].map(|c| c.max(32))
+}
+
+/// Writes `channels`, each a name and the values of a `width` by `height` image in row-major
+/// order, to `output` as a single part, scanline, uncompressed OpenEXR file of 32 bit floats.
+/// Layers are channels whose names share a prefix before a dot, such as `albedo.R`.
+fn write_exr<Output: Write>(
+    output: &mut Output,
+    width: u64,
+    height: u64,
+    channels: &[(String, Vec<f32>)],
+) -> Result<()> {
+    // readers expect the channels in alphabetical order, both in the header and in each line.
+    let mut channels: Vec<_> = channels.iter().collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    // version 2, a single part of scanlines.
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        for text in [name, kind] {
+            header.extend_from_slice(text.as_bytes());
+            header.push(0);
+        }
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    let mut list = Vec::new();
+    for (name, _) in &channels {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        // 32 bit floats, not perceptually linear, sampled at every pixel.
+        list.extend_from_slice(&2i32.to_le_bytes());
+        list.extend_from_slice(&[0; 4]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    attribute("channels", "chlist", &list);
+    attribute("compression", "compression", &[0]);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+    output.write_all(&header)?;
+
+    // each line is a chunk of its own, found through a table of where each starts.
+    let line_size = channels.len() * width as usize * 4;
+    let start = header.len() + 8 * height as usize;
+    for y in 0..height as usize {
+        let offset = start + y * (8 + line_size);
+        output.write_all(&(offset as u64).to_le_bytes())?;
+    }
+
+    let width = width as usize;
+    for y in 0..height as usize {
+        output.write_all(&(y as i32).to_le_bytes())?;
+        output.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, values) in &channels {
+            for value in &values[y * width..(y + 1) * width] {
+                output.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
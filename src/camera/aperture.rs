@@ -0,0 +1,225 @@
+use std::{
+    f32::consts::TAU,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::{sampler::Sampler, vec3::Vec3};
+
+/// How many times to retry sampling the aperture before giving up on a pixel whose view of the
+/// lens is almost entirely vignetted.
+const MAX_REJECTION_ATTEMPTS: usize = 64;
+/// The smallest anamorphic squeeze, which keeps the aperture from being stretched infinitely wide.
+const MIN_ANAMORPHIC_SQUEEZE: f32 = 0.01;
+
+/// The shape of the opening in a lens, which gives out-of-focus highlights their shape.
+#[derive(Debug, Clone, Default)]
+pub enum ApertureShape {
+    #[default]
+    Circle,
+    /// A regular polygon, as formed by an iris with straight `blades`, rotated by `rotation`
+    /// degrees.  Fewer than 3 blades are taken as 3.
+    Polygon { blades: u32, rotation: f32 },
+    /// An arbitrary shape, given by an image.
+    Mask(ApertureMask),
+}
+
+/// A grayscale image of an aperture, stretched over the square enclosing the lens.  Brighter
+/// pixels let through more light.
+#[derive(Debug, Clone)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl ApertureMask {
+    /// Loads a mask from a PGM or PPM image (in either plain or raw format).  Color images are
+    /// converted to grayscale by averaging their channels.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        Self::from_pnm(&data)
+    }
+
+    fn from_pnm(data: &[u8]) -> Result<Self> {
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+        // the header is made of whitespace separated tokens, with comments running from `#` to
+        // the end of the line.
+        let mut position = 0;
+        let mut next_token = || -> Result<&[u8]> {
+            loop {
+                while position < data.len() && data[position].is_ascii_whitespace() {
+                    position += 1;
+                }
+                if position < data.len() && data[position] == b'#' {
+                    while position < data.len() && data[position] != b'\n' {
+                        position += 1;
+                    }
+                    continue;
+                }
+                break;
+            }
+            let start = position;
+            while position < data.len() && !data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(invalid("unexpected end of image"));
+            }
+            Ok(&data[start..position])
+        };
+        let parse = |token: &[u8]| -> Result<usize> {
+            std::str::from_utf8(token)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| invalid("expected a number"))
+        };
+
+        let magic = next_token()?.to_vec();
+        let channels = match magic.as_slice() {
+            b"P2" | b"P5" => 1,
+            b"P3" | b"P6" => 3,
+            _ => return Err(invalid("not a PGM or PPM image")),
+        };
+        let width = parse(next_token()?)?;
+        let height = parse(next_token()?)?;
+        let max_value = parse(next_token()?)?.max(1) as f32;
+        if width == 0 || height == 0 {
+            return Err(invalid("image has no pixels"));
+        }
+        let samples = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .ok_or_else(|| invalid("image is too large"))?;
+
+        let raw: Vec<f32> = if magic == b"P5" || magic == b"P6" {
+            // a single whitespace character separates the header from the pixel data.
+            let start = position + 1;
+            let bytes_per_sample = if max_value > 255.0 { 2 } else { 1 };
+            let end = start + samples * bytes_per_sample;
+            if end > data.len() {
+                return Err(invalid("unexpected end of image"));
+            }
+            data[start..end]
+                .chunks(bytes_per_sample)
+                .map(|c| c.iter().fold(0u32, |acc, &b| (acc << 8) | u32::from(b)) as f32)
+                .collect()
+        } else {
+            (0..samples)
+                .map(|_| next_token().and_then(parse).map(|v| v as f32))
+                .collect::<Result<_>>()?
+        };
+
+        let values = raw
+            .chunks(channels)
+            .map(|c| c.iter().sum::<f32>() / (channels as f32 * max_value))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    /// How much light the mask lets through at `(x, y)`, where both range from `-1` to `1`.
+    fn transmission(&self, x: f32, y: f32) -> f32 {
+        let column = (((x + 1.0) * 0.5 * self.width as f32) as usize).min(self.width - 1);
+        let row = (((1.0 - y) * 0.5 * self.height as f32) as usize).min(self.height - 1);
+        self.values[row * self.width + column]
+    }
+}
+
+/// The aperture of a camera's lens.
+#[derive(Debug, Clone)]
+pub struct Aperture {
+    shape: ApertureShape,
+    cat_eye: f32,
+    anamorphic_squeeze: f32,
+}
+
+impl Default for Aperture {
+    fn default() -> Self {
+        Self {
+            shape: ApertureShape::default(),
+            cat_eye: 0.0,
+            anamorphic_squeeze: 1.0,
+        }
+    }
+}
+
+impl Aperture {
+    pub fn new(shape: ApertureShape) -> Self {
+        Self {
+            shape,
+            ..Default::default()
+        }
+    }
+
+    /// Simulates the barrel of the lens blocking part of the aperture away from the center of the
+    /// image, so out-of-focus highlights near the edges take on a cat's eye shape.  At `0` there
+    /// is no vignetting; at `1` the aperture is cut off halfway in the corners of the image.
+    pub fn with_cat_eye(mut self, cat_eye: f32) -> Self {
+        self.cat_eye = cat_eye.max(0.0);
+        self
+    }
+
+    /// Squeezes the aperture horizontally by `squeeze`, as anamorphic lenses do, so out-of-focus
+    /// highlights become tall ovals.  Defaults to 1, for a spherical lens.  Squeezes below 0.01
+    /// are taken as 0.01.
+    pub fn with_anamorphic_squeeze(mut self, squeeze: f32) -> Self {
+        self.anamorphic_squeeze = squeeze.max(MIN_ANAMORPHIC_SQUEEZE);
+        self
+    }
+
+    /// Samples a point on the aperture, scaled to fit within the unit disc, as seen from the
+    /// point `(x, y)` of the image.  Image coordinates range from `-1` to `1`, from the left to
+    /// the right and from the top to the bottom.
+    pub(super) fn sample(&self, x: f32, y: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        // the lens barrel acts as a second, offset aperture, which also has to let the light in.
+        let barrel_offset = (self.cat_eye * 0.5 * std::f32::consts::SQRT_2).min(2.0);
+        let barrel_center = (x * barrel_offset, -y * barrel_offset);
+
+        for _ in 0..MAX_REJECTION_ATTEMPTS {
+            let Some(p) = self.sample_shape(sampler) else {
+                continue;
+            };
+            let (dx, dy) = (p.x() - barrel_center.0, p.y() - barrel_center.1);
+            if self.cat_eye > 0.0 && dx * dx + dy * dy > 1.0 {
+                continue;
+            }
+            return Vec3::new(p.x() / self.anamorphic_squeeze, p.y(), 0.0);
+        }
+
+        Vec3::default()
+    }
+
+    /// Samples a point within the shape of the aperture, or `None` if a sample was rejected and
+    /// another one should be taken.
+    fn sample_shape(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        match &self.shape {
+            ApertureShape::Circle => Some(Vec3::sample_unit_disc(sampler.get_2d())),
+            ApertureShape::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                // pick one of the triangles fanning out from the center, then a point within it.
+                let wedge = TAU / blades as f32;
+                let k = ((sampler.get_1d() * blades as f32) as u32).min(blades - 1);
+                let start = rotation.to_radians() + k as f32 * wedge;
+                let a = Vec3::new(start.cos(), start.sin(), 0.0);
+                let b = Vec3::new((start + wedge).cos(), (start + wedge).sin(), 0.0);
+
+                let (mut u, mut v) = sampler.get_2d();
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+                Some(u * a + v * b)
+            }
+            ApertureShape::Mask(mask) => {
+                let (u, v) = sampler.get_2d();
+                let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+                (sampler.get_1d() < mask.transmission(x, y)).then(|| Vec3::new(x, y, 0.0))
+            }
+        }
+    }
+}
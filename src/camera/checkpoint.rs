@@ -0,0 +1,160 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::film::Pixel;
+use crate::util::write_atomically;
+
+/// Identifies checkpoint files, and the version of their format.
+const MAGIC: &[u8; 8] = b"RTCKPT03";
+
+/// Settings for saving the samples taken so far to a checkpoint file every so often, so that a
+/// render that's stopped can be resumed later.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    /// How long to wait at least between saving checkpoints.  Checkpoints are only saved between
+    /// passes over the image, and once more when the render finishes.
+    pub interval: Duration,
+    /// Whether to carry on from the samples in the checkpoint at `path`, rather than starting
+    /// afresh.
+    pub resume: bool,
+}
+
+impl Checkpoint {
+    /// Creates settings that save a checkpoint to `path` every minute.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            interval: Duration::from_secs(60),
+            resume: false,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+/// The contents of a checkpoint file.
+pub(super) struct CheckpointData {
+    /// A hash of everything that determines what the image looks like, which has to match for a
+    /// render to be resumed.
+    pub(super) settings_hash: u64,
+    /// The seed the samplers were created with.
+    pub(super) seed: u64,
+    /// The samples taken for each pixel of each eye rendered, along with their AOV sums.
+    pub(super) eyes: Vec<Vec<Pixel>>,
+}
+
+impl CheckpointData {
+    /// Replaces the checkpoint at `path` with this one.
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        let pixels = self.eyes.first().map_or(0, Vec::len);
+        // pixels that have been sampled all have the same number of AOV channels.
+        let channels = self
+            .eyes
+            .iter()
+            .flatten()
+            .map(|pixel| pixel.aovs.len())
+            .max()
+            .unwrap_or(0);
+        let pixel_bytes = Pixel::BYTES + 4 * channels;
+        let mut bytes =
+            Vec::with_capacity(MAGIC.len() + 40 + self.eyes.len() * pixels * pixel_bytes);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.settings_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.eyes.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(pixels as u64).to_le_bytes());
+        bytes.extend_from_slice(&(channels as u64).to_le_bytes());
+        for pixel in self.eyes.iter().flatten() {
+            pixel.write_bytes(&mut bytes);
+            pixel.write_aov_bytes(channels, &mut bytes);
+        }
+        write_atomically(path, &bytes)
+    }
+
+    /// Reads the checkpoint at `path`, which has to have been saved by a render with the same
+    /// `settings_hash` and `seed`.
+    pub(super) fn read(path: &Path, settings_hash: u64, seed: u64) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+
+        let header_length = MAGIC.len() + 40;
+        if bytes.len() < header_length || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(invalid(format!("{} is not a checkpoint", path.display())));
+        }
+        let header = |k: usize| {
+            let start = MAGIC.len() + 8 * k;
+            u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap())
+        };
+
+        if header(0) != settings_hash {
+            return Err(invalid(format!(
+                "{} is a checkpoint of a different scene, camera or set of AOVs",
+                path.display()
+            )));
+        }
+        if header(1) != seed {
+            return Err(invalid(format!(
+                "{} is a checkpoint of a render with seed {}, not {}",
+                path.display(),
+                header(1),
+                seed
+            )));
+        }
+
+        // the header can't be trusted to give sizes that fit together, or at all.
+        let (eyes, pixels) = (header(2) as usize, header(3) as usize);
+        let pixel_bytes = (header(4) as usize)
+            .checked_mul(4)
+            .and_then(|n| n.checked_add(Pixel::BYTES));
+        let length = pixel_bytes
+            .and_then(|n| n.checked_mul(pixels))
+            .and_then(|n| n.checked_mul(eyes))
+            .and_then(|n| n.checked_add(header_length));
+        if pixels == 0 || length != Some(bytes.len()) {
+            return Err(invalid(format!("{} is corrupt", path.display())));
+        }
+        let pixel_bytes = pixel_bytes.unwrap();
+        let eyes = bytes[header_length..]
+            .chunks(pixels * pixel_bytes)
+            .map(|eye| {
+                eye.chunks(pixel_bytes)
+                    .map(|bytes| {
+                        let (samples, aovs) = bytes.split_at(Pixel::BYTES);
+                        let mut pixel = Pixel::from_bytes(samples);
+                        pixel.read_aov_bytes(aovs);
+                        pixel
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            settings_hash,
+            seed,
+            eyes,
+        })
+    }
+}
+
+/// Hashes `parts` with 64 bit FNV-1a, which unlike the standard library's hasher is guaranteed
+/// to give the same hash from one build to the next.
+pub(super) fn hash(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xcbf2_9ce4_8422_2325, |h, &byte| {
+            (h ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
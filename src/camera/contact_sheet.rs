@@ -0,0 +1,57 @@
+use std::num::NonZeroU32;
+
+use crate::vec3::Color;
+
+/// Frames of the same size laid out in a grid, left to right and top to bottom, for seeing a
+/// whole sequence at a glance.
+#[derive(Debug, Clone)]
+pub struct ContactSheet {
+    columns: u64,
+    frame_width: u64,
+    frame_height: u64,
+    width: u64,
+    height: u64,
+    colors: Vec<Color>,
+}
+
+impl ContactSheet {
+    /// A black sheet with room for `frames` frames, each `frame_width` by `frame_height` pixels,
+    /// in a grid about as many frames wide as high.
+    pub fn new(frames: u64, frame_width: u64, frame_height: u64) -> Self {
+        let frames = frames.max(1);
+        let columns = (frames as f64).sqrt().ceil() as u64;
+        let rows = frames.div_ceil(columns);
+
+        let (width, height) = (columns * frame_width, rows * frame_height);
+        Self {
+            columns,
+            frame_width,
+            frame_height,
+            width,
+            height,
+            colors: vec![Color::default(); (width * height) as usize],
+        }
+    }
+
+    /// Places the `colors` of a frame, in row-major order, at the `index`th place on the sheet.
+    pub fn add_frame(&mut self, index: u64, colors: &[Color]) {
+        let x = index % self.columns * self.frame_width;
+        let y = index / self.columns * self.frame_height;
+
+        for (row, colors) in colors.chunks(self.frame_width as usize).enumerate() {
+            let start = ((y + row as u64) * self.width + x) as usize;
+            if let Some(pixels) = self.colors.get_mut(start..start + colors.len()) {
+                pixels.copy_from_slice(colors);
+            }
+        }
+    }
+
+    /// Writes the sheet to `output` as a PPM.
+    pub fn write_ppm<Output: std::io::Write>(&self, output: &mut Output) -> std::io::Result<()> {
+        write!(output, "P3\n{} {}\n255\n", self.width, self.height)?;
+        for color in &self.colors {
+            color.write_ppm(output, NonZeroU32::MIN)?;
+        }
+        Ok(())
+    }
+}
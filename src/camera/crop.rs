@@ -0,0 +1,90 @@
+use std::io::{Error, ErrorKind, Result};
+
+use super::tiles::Tile;
+
+/// The corners of a region of the image, counting from its top left.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CropWindow {
+    /// The pixels from `(x_min, y_min)` up to but not including `(x_max, y_max)`.
+    Pixels {
+        x_min: u64,
+        y_min: u64,
+        x_max: u64,
+        y_max: u64,
+    },
+    /// The same, as fractions of the image's width and height, from 0 to 1.  Pixels partly
+    /// inside the window are rendered.
+    Normalized {
+        x_min: f32,
+        y_min: f32,
+        x_max: f32,
+        y_max: f32,
+    },
+}
+
+/// Settings for rendering only a region of the image, seen exactly as it would be in the full
+/// image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Crop {
+    pub window: CropWindow,
+    /// Whether to place the region in an image of the full size, black everywhere else, rather
+    /// than writing an image of just the region.
+    pub full_frame: bool,
+}
+
+impl Crop {
+    pub fn new(window: CropWindow) -> Self {
+        Self {
+            window,
+            full_frame: false,
+        }
+    }
+
+    pub fn with_full_frame(mut self, full_frame: bool) -> Self {
+        self.full_frame = full_frame;
+        self
+    }
+
+    /// The pixels of a `width` by `height` image inside the window, which fails if the window
+    /// doesn't cover any of them.
+    pub fn region(&self, width: u64, height: u64) -> Result<Tile> {
+        let (x_min, y_min, x_max, y_max) = match self.window {
+            CropWindow::Pixels {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (x_min, y_min, x_max, y_max),
+            CropWindow::Normalized {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => {
+                let scale = |v: f32, size: u64, round: fn(f32) -> f32| {
+                    round(v.clamp(0.0, 1.0) * size as f32) as u64
+                };
+                (
+                    scale(x_min, width, f32::floor),
+                    scale(y_min, height, f32::floor),
+                    scale(x_max, width, f32::ceil),
+                    scale(y_max, height, f32::ceil),
+                )
+            }
+        };
+
+        let (x_max, y_max) = (x_max.min(width), y_max.min(height));
+        if x_min >= x_max || y_min >= y_max {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the crop window lies outside the {width}x{height} image"),
+            ));
+        }
+        Ok(Tile {
+            x: x_min,
+            y: y_min,
+            width: x_max - x_min,
+            height: y_max - y_min,
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use rayon::prelude::*;
+
+use super::aov::{Aov, Layer};
+use crate::vec3::{Color, Vec3};
+
+/// How far apart, relative to the noise in them, the colors of two pixels have to be before
+/// they're kept from being averaged, at a strength of 1.
+const COLOR_SENSITIVITY: f32 = 1.5;
+/// How much the albedo of two pixels can differ before they're kept apart.
+const ALBEDO_SIGMA: f32 = 0.1;
+/// How much the normals of two pixels can differ before they're kept apart.
+const NORMAL_SIGMA: f32 = 0.25;
+/// How much the depth of two pixels can differ, as a fraction of the nearest, before they're
+/// kept apart.
+const DEPTH_SIGMA: f32 = 0.05;
+
+/// Settings for smoothing away the noise left in the image after sampling.
+///
+/// Each pixel is averaged with the neighbours whose colors differ from its own by no more than
+/// the noise in them would explain, and which see a surface much like its own: of the same
+/// albedo, facing the same way, at the same depth.  Edges and textures follow those surfaces, so
+/// they stay sharp however noisy the colors are.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Denoise {
+    /// How many pixels away to look for neighbours to average with.
+    pub radius: u32,
+    /// How readily neighbours of different colors are averaged, and so how smooth the image
+    /// comes out.  Lower strengths keep more detail, and more noise.
+    pub strength: f32,
+}
+
+impl Default for Denoise {
+    fn default() -> Self {
+        Self {
+            radius: 6,
+            strength: 1.0,
+        }
+    }
+}
+
+impl Denoise {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_radius(mut self, radius: u32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Sets how readily neighbours of different colors are averaged.  Negative strengths are
+    /// taken as 0.
+    pub fn with_strength(mut self, strength: f32) -> Self {
+        self.strength = strength.max(0.0);
+        self
+    }
+
+    /// The buffers the denoiser is guided by.
+    pub(super) const GUIDES: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Depth];
+
+    /// Denoises the `colors` of a `width` by `height` image, given the variance of the mean of
+    /// each pixel's samples and the `layers` gathered for it, in row-major order.  Guides that
+    /// weren't gathered are left out.
+    pub(super) fn apply(
+        &self,
+        width: u64,
+        height: u64,
+        colors: &[Color],
+        variances: &[f32],
+        layers: &[Layer],
+    ) -> Vec<Color> {
+        let guide = |buffer: Aov| {
+            layers
+                .iter()
+                .find(|layer| layer.buffer == buffer)
+                .map(|layer| &layer.values[..])
+        };
+        let (albedo, normal, depth) = (guide(Aov::Albedo), guide(Aov::Normal), guide(Aov::Depth));
+        let vector = |values: &[f32], k: usize| {
+            Vec3::new(values[3 * k], values[3 * k + 1], values[3 * k + 2])
+        };
+
+        let (width, height) = (width as i64, height as i64);
+        let variances = box_blur(variances, width, height);
+        let radius = i64::from(self.radius);
+        let spatial = 0.5 / (0.5 * self.radius.max(1) as f32).powi(2);
+        let sensitivity = (COLOR_SENSITIVITY * self.strength).powi(2);
+
+        let mut denoised = vec![Color::default(); colors.len()];
+        denoised
+            .par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let y = y as i64;
+                for (x, denoised) in row.iter_mut().enumerate() {
+                    let x = x as i64;
+                    let p = (y * width + x) as usize;
+
+                    let mut sum = Color::default();
+                    let mut total = 0.0;
+                    for qy in (y - radius).max(0)..=(y + radius).min(height - 1) {
+                        for qx in (x - radius).max(0)..=(x + radius).min(width - 1) {
+                            let q = (qy * width + qx) as usize;
+
+                            let (dx, dy) = ((qx - x) as f32, (qy - y) as f32);
+                            let mut distance = spatial * (dx * dx + dy * dy);
+
+                            // the difference in color, beyond what the noise in both explains.
+                            // pixels with too few samples to tell how noisy they are could be
+                            // anything, so their colors are left out.
+                            let variance = variances[p] + variances[q];
+                            if variance.is_finite() {
+                                let difference = colors[p] - colors[q];
+                                let excess = (difference.len_squared() / 3.0 - variance).max(0.0);
+                                distance += excess / (1e-4 + sensitivity * variance);
+                            }
+
+                            if let Some(albedo) = albedo {
+                                let difference = vector(albedo, p) - vector(albedo, q);
+                                distance += difference.len_squared() / (2.0 * ALBEDO_SIGMA.powi(2));
+                            }
+                            if let Some(normal) = normal {
+                                let difference = vector(normal, p) - vector(normal, q);
+                                distance += difference.len_squared() / (2.0 * NORMAL_SIGMA.powi(2));
+                            }
+                            if let Some(depth) = depth {
+                                let nearest = depth[p].min(depth[q]).max(1e-3);
+                                let difference = (depth[p] - depth[q]) / nearest;
+                                distance += difference * difference / (2.0 * DEPTH_SIGMA.powi(2));
+                            }
+
+                            let weight = (-distance).exp();
+                            sum += weight * colors[q];
+                            total += weight;
+                        }
+                    }
+                    // a pixel always has a weight of 1 for itself, so the total is never 0.
+                    *denoised = sum / total;
+                }
+            });
+        denoised
+    }
+}
+
+/// Averages each of the `values` of a `width` by `height` image with its neighbours, since the
+/// variance of a pixel's samples is too rough an estimate of the noise in it by itself.
+fn box_blur(values: &[f32], width: i64, height: i64) -> Vec<f32> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (mut sum, mut count) = (0.0, 0.0);
+            for qy in (y - 1).max(0)..=(y + 1).min(height - 1) {
+                for qx in (x - 1).max(0)..=(x + 1).min(width - 1) {
+                    sum += values[(qy * width + qx) as usize];
+                    count += 1.0;
+                }
+            }
+            sum / count
+        })
+        .collect()
+}
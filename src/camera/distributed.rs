@@ -0,0 +1,390 @@
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind, Read, Result, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use super::{film::Pixel, tiles::Tile};
+
+/// Identifies the renderer's protocol, and its version, at the start of each connection.
+const MAGIC: &[u8; 8] = b"RTDIST02";
+
+/// How long to wait before looking for new workers, or for work given back by workers that
+/// dropped out.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The largest job a worker accepts, so that a peer that isn't a coordinator can't make it
+/// allocate without bound.
+const MAX_JOB_BYTES: u64 = 16 << 20;
+
+/// Hands out the tiles of an image to workers that connect to it over TCP, and gathers up the
+/// pixels they render.
+#[derive(Debug)]
+pub struct Coordinator {
+    listener: TcpListener,
+    /// What each worker needs to set up the scene, in whatever form the caller chooses.
+    job: Vec<u8>,
+    /// How long to wait for a worker to answer before taking it to have dropped out, which has
+    /// to be longer than it takes to render a tile.
+    pub timeout: Duration,
+}
+
+impl Coordinator {
+    /// Listens for workers on `address`, giving each of them `job` when it connects.  Workers
+    /// that take more than 10 minutes to answer are taken to have dropped out.
+    pub fn bind(address: impl ToSocketAddrs, job: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            job,
+            timeout: Duration::from_secs(600),
+        })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts workers until all of `work` is done, each served on a thread of its own.  Tiles of
+    /// an image `width` pixels wide are only given to workers whose scene hashes to
+    /// `scene_hash`.
+    pub(super) fn run(
+        &self,
+        scene_hash: u64,
+        width: u64,
+        work: &Work,
+        progress_bar: &indicatif::ProgressBar,
+    ) -> Result<()> {
+        self.listener.set_nonblocking(true)?;
+        std::thread::scope(|scope| {
+            while !work.is_done() {
+                match self.listener.accept() {
+                    Ok((stream, address)) => {
+                        scope.spawn(move || {
+                            if let Err(e) =
+                                self.serve(stream, scene_hash, width, work, progress_bar)
+                            {
+                                progress_bar
+                                    .suspend(|| eprintln!("Worker {address} dropped out: {e}"));
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(POLL_INTERVAL)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Gives the worker at the other end of `stream` tiles until there are none left, putting
+    /// back the tile it's working on if it drops out.  Workers that stop answering without
+    /// hanging up, such as those whose machines lost power, time out like any other drop out.
+    fn serve(
+        &self,
+        mut stream: TcpStream,
+        scene_hash: u64,
+        width: u64,
+        work: &Work,
+        progress_bar: &indicatif::ProgressBar,
+    ) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        // sockets can't time out immediately.
+        let timeout = self.timeout.max(Duration::from_millis(1));
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.write_all(MAGIC)?;
+        write_u64(&mut stream, self.job.len() as u64)?;
+        stream.write_all(&self.job)?;
+
+        if read_u64(&mut stream)? != scene_hash {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the worker set up a different scene",
+            ));
+        }
+
+        loop {
+            let Some((eye, tile)) = work.take() else {
+                if work.is_done() {
+                    return Ok(());
+                }
+                // other workers may still drop out and give their tiles back.
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
+            match request_tile(&mut stream, eye, &tile) {
+                Ok(pixels) => {
+                    work.finish(eye, &tile, width, &pixels);
+                    progress_bar.inc(pixels.len() as u64);
+                }
+                Err(e) => {
+                    work.give_back(eye, tile);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Asks the worker at the other end of `stream` to render `tile` as seen by the `eye`th eye, and
+/// waits for its pixels.
+fn request_tile(stream: &mut TcpStream, eye: usize, tile: &Tile) -> Result<Vec<Pixel>> {
+    for value in [eye as u64, tile.x, tile.y, tile.width, tile.height] {
+        write_u64(stream, value)?;
+    }
+
+    let mut bytes = vec![0; (tile.width * tile.height) as usize * Pixel::BYTES];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes.chunks(Pixel::BYTES).map(Pixel::from_bytes).collect())
+}
+
+/// A connection to a coordinator, for rendering the tiles it asks for.
+#[derive(Debug)]
+pub struct Worker {
+    stream: TcpStream,
+    job: Vec<u8>,
+}
+
+impl Worker {
+    /// Connects to the coordinator at `address`, and waits for the job it gives.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        let mut magic = [0; MAGIC.len()];
+        stream.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not connected to a coordinator",
+            ));
+        }
+        let length = read_u64(&mut stream)?;
+        if length > MAX_JOB_BYTES {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("the coordinator's job of {length} bytes is too large"),
+            ));
+        }
+        let mut job = vec![0; length as usize];
+        stream.read_exact(&mut job)?;
+
+        Ok(Self { stream, job })
+    }
+
+    /// What the coordinator needs the worker to set up the scene from.
+    pub fn job(&self) -> &[u8] {
+        &self.job
+    }
+
+    /// Tells the coordinator which scene the worker set up from the job.
+    pub(super) fn send_scene_hash(&mut self, scene_hash: u64) -> Result<()> {
+        write_u64(&mut self.stream, scene_hash)
+    }
+
+    /// Waits for the next tile to render and the index of the eye to render it for, or `None`
+    /// once the coordinator has hung up.
+    pub(super) fn next_tile(&mut self) -> Result<Option<(usize, Tile)>> {
+        let mut values = [0; 5];
+        for (k, value) in values.iter_mut().enumerate() {
+            match read_u64(&mut self.stream) {
+                Ok(v) => *value = v,
+                Err(e) if k == 0 && e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let [eye, x, y, width, height] = values;
+        Ok(Some((
+            eye as usize,
+            Tile {
+                x,
+                y,
+                width,
+                height,
+            },
+        )))
+    }
+
+    /// Sends the pixels of the last tile asked for back to the coordinator.
+    pub(super) fn send_pixels(&mut self, pixels: &[Pixel]) -> Result<()> {
+        let mut bytes = Vec::with_capacity(pixels.len() * Pixel::BYTES);
+        for pixel in pixels {
+            pixel.write_bytes(&mut bytes);
+        }
+        self.stream.write_all(&bytes)
+    }
+}
+
+/// The tiles of each eye's image still to be handed out, and the pixels of those rendered.
+pub(super) struct Work {
+    queue: Mutex<VecDeque<(usize, Tile)>>,
+    /// How many tiles haven't been rendered yet, including those being rendered.
+    remaining: AtomicUsize,
+    eyes: Mutex<Vec<Vec<Pixel>>>,
+}
+
+impl Work {
+    /// Work for `pixels` pixels of each of `eyes` eyes, split into `tiles`.
+    pub(super) fn new(eyes: usize, pixels: usize, tiles: &[Tile]) -> Self {
+        let queue: VecDeque<_> = (0..eyes)
+            .flat_map(|eye| tiles.iter().map(move |&tile| (eye, tile)))
+            .collect();
+        Self {
+            remaining: AtomicUsize::new(queue.len()),
+            queue: Mutex::new(queue),
+            eyes: Mutex::new(vec![vec![Pixel::default(); pixels]; eyes]),
+        }
+    }
+
+    fn take(&self) -> Option<(usize, Tile)> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    fn give_back(&self, eye: usize, tile: Tile) {
+        self.queue.lock().unwrap().push_front((eye, tile));
+    }
+
+    /// Puts the rendered `pixels` of `tile` in an image `width` pixels wide.
+    fn finish(&self, eye: usize, tile: &Tile, width: u64, pixels: &[Pixel]) {
+        let mut eyes = self.eyes.lock().unwrap();
+        for (index, pixel) in tile.pixel_indices(width).zip(pixels) {
+            eyes[eye][index] = pixel.clone();
+        }
+        self.remaining.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn is_done(&self) -> bool {
+        self.remaining.load(Ordering::SeqCst) == 0
+    }
+
+    /// The pixels of each eye, once all the work is done.
+    pub(super) fn into_eyes(self) -> Vec<Vec<Pixel>> {
+        self.eyes.into_inner().unwrap()
+    }
+}
+
+fn write_u64(stream: &mut TcpStream, value: u64) -> Result<()> {
+    stream.write_all(&value.to_le_bytes())
+}
+
+fn read_u64(stream: &mut TcpStream) -> Result<u64> {
+    let mut bytes = [0; 8];
+    stream.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+    use crate::{
+        camera::{CameraBuilder, Tiles},
+        geometry::{HittableList, Sphere},
+        light::{LightList, PointLight},
+        material::Lambertian,
+        sampler::SamplerKind,
+        vec3::{Color, Point3},
+    };
+
+    fn bytes(eyes: &[Vec<Pixel>]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for pixel in eyes.iter().flatten() {
+            pixel.write_bytes(&mut bytes);
+        }
+        bytes
+    }
+
+    #[test]
+    fn tiles_of_workers_that_drop_out_are_rendered_by_others() {
+        let ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let center = Lambertian::new(Color::new(0.7, 0.3, 0.3));
+        let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &ground);
+        let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &center);
+        let mut world = HittableList::default();
+        world.add(&ground_sphere);
+        world.add(&center_sphere);
+        let light = PointLight::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0));
+        let mut lights = LightList::default();
+        lights.add(&light);
+
+        let camera = CameraBuilder::default()
+            .with_image_width(24)
+            .with_aspect_ratio(1.5)
+            .with_samples_per_pixel(4)
+            .with_sampler(SamplerKind::Sobol)
+            .with_tiles(Tiles::new(8))
+            .look_from(Point3::new(0.0, 0.0, 1.0))
+            .look_at(Point3::new(0.0, 0.0, -1.0))
+            .build()
+            .unwrap();
+        let scene_hash = camera.distributed_hash(&world, &lights);
+
+        let coordinator = Coordinator::bind("127.0.0.1:0", b"job".to_vec())
+            .unwrap()
+            .with_timeout(Duration::from_millis(500));
+        let address = coordinator.local_addr().unwrap();
+        let work = Work::new(1, camera.num_pixels(), &camera.tiles);
+
+        thread::scope(|scope| {
+            let (took_tile, tiles_taken) = mpsc::channel();
+            let (finished, wait_for_finish) = mpsc::channel::<()>();
+
+            // one worker hangs up in the middle of its tile, and another stops answering.
+            let hang_up = took_tile.clone();
+            scope.spawn(move || {
+                let mut worker = Worker::connect(address).unwrap();
+                assert_eq!(worker.job(), b"job");
+                worker.send_scene_hash(scene_hash).unwrap();
+                assert!(worker.next_tile().unwrap().is_some());
+                drop(worker);
+                hang_up.send(()).unwrap();
+            });
+            scope.spawn(move || {
+                let mut worker = Worker::connect(address).unwrap();
+                worker.send_scene_hash(scene_hash).unwrap();
+                assert!(worker.next_tile().unwrap().is_some());
+                took_tile.send(()).unwrap();
+                // keep the connection open without answering until the render is over.
+                wait_for_finish.recv().ok();
+            });
+
+            // the tiles given back go to a worker that renders everything it's given.
+            let camera = &camera;
+            let (world, lights) = (&world, &lights);
+            scope.spawn(move || {
+                tiles_taken.recv().unwrap();
+                tiles_taken.recv().unwrap();
+                camera
+                    .work(world, lights, Worker::connect(address).unwrap())
+                    .unwrap();
+            });
+
+            let progress_bar = indicatif::ProgressBar::hidden();
+            coordinator
+                .run(scene_hash, camera.crop.width, &work, &progress_bar)
+                .unwrap();
+            finished.send(()).unwrap();
+        });
+
+        let local = camera
+            .render_eyes(&world, &lights, &camera.eyes(), None)
+            .unwrap();
+        assert!(bytes(&work.into_eyes()) == bytes(&local));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::vec3::Color;
+
+/// The samples taken for a single pixel so far.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Pixel {
+    /// The sum of the samples' radiance, each weighted by the filter.
+    radiance: Color,
+    /// The sum of the filter's weights for the samples.
+    filter_weight: f32,
+    /// The sum of the samples' radiance, ignoring the filter.
+    unweighted: Color,
+    samples: u32,
+    /// The mean of the samples' weighted luminance.
+    mean: f32,
+    /// The sum of squared differences of the samples' weighted luminance from their mean, from
+    /// which Welford's method finds their variance without keeping every sample around.
+    squared_deviations: f32,
+    /// Whether the pixel needs no more samples.
+    pub(super) done: bool,
+    /// The sums of the samples' values for each AOV, in the order [`super::Aovs`] gives them, or
+    /// nothing without AOVs.
+    pub(super) aovs: Vec<f32>,
+}
+
+impl Pixel {
+    /// Adds a sample of `radiance`, which the filter gives a weight of `filter_weight`.
+    pub(super) fn add_sample(&mut self, radiance: Color, filter_weight: f32) {
+        self.unweighted += radiance;
+        let radiance = filter_weight * radiance;
+        self.radiance += radiance;
+        self.filter_weight += filter_weight;
+        self.samples += 1;
+
+        let luminance = luminance(radiance);
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f32;
+        self.squared_deviations += delta * (luminance - self.mean);
+    }
+
+    pub(super) fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// The pixel's color so far.
+    ///
+    /// This divides by the filter's weights rather than the number of samples, so that samples
+    /// with negative weights don't add noise to areas of flat color.  Filters with negative lobes
+    /// can leave a pixel with few samples weighing nothing at all, though, which then takes the
+    /// plain mean of its samples instead.
+    pub(super) fn color(&self) -> Color {
+        if self.filter_weight > 0.0 {
+            self.radiance / self.filter_weight
+        } else if self.samples > 0 {
+            self.unweighted / self.samples as f32
+        } else {
+            Color::default()
+        }
+    }
+
+    /// The mean of the `k`th of the pixel's AOV values so far, weighted by the filter like its
+    /// color.
+    pub(super) fn aov(&self, k: usize) -> f32 {
+        match self.aovs.get(k) {
+            Some(sum) if self.filter_weight > 0.0 => sum / self.filter_weight,
+            _ => 0.0,
+        }
+    }
+
+    /// The pixel's luminance so far.
+    pub(super) fn luminance(&self) -> f32 {
+        luminance(self.color())
+    }
+
+    /// How many bytes a pixel takes up in a checkpoint.
+    pub(super) const BYTES: usize = 10 * 4;
+
+    /// Appends the pixel's samples to `bytes`, for saving in a checkpoint.
+    pub(super) fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        let values = [
+            self.radiance.x(),
+            self.radiance.y(),
+            self.radiance.z(),
+            self.filter_weight,
+            self.unweighted.x(),
+            self.unweighted.y(),
+            self.unweighted.z(),
+            f32::from_bits(self.samples),
+            self.mean,
+            self.squared_deviations,
+        ];
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Reads the samples of a pixel written by [`Pixel::write_bytes`].  Whether the pixel is done
+    /// isn't saved, since that depends on how many samples the resumed render takes, and its AOVs
+    /// are read separately by [`Pixel::read_aov_bytes`].
+    pub(super) fn from_bytes(bytes: &[u8]) -> Self {
+        let value = |k: usize| f32::from_le_bytes(bytes[4 * k..4 * k + 4].try_into().unwrap());
+        Self {
+            radiance: Color::new(value(0), value(1), value(2)),
+            filter_weight: value(3),
+            unweighted: Color::new(value(4), value(5), value(6)),
+            samples: value(7).to_bits(),
+            mean: value(8),
+            squared_deviations: value(9),
+            done: false,
+            aovs: Vec::new(),
+        }
+    }
+
+    /// Appends the pixel's sums for `channels` AOV channels to `bytes`, for saving in a
+    /// checkpoint.  Pixels without samples have no sums yet, and are saved with sums of 0.
+    pub(super) fn write_aov_bytes(&self, channels: usize, bytes: &mut Vec<u8>) {
+        for k in 0..channels {
+            let sum = self.aovs.get(k).copied().unwrap_or(0.0);
+            bytes.extend_from_slice(&sum.to_le_bytes());
+        }
+    }
+
+    /// Reads the AOV sums written by [`Pixel::write_aov_bytes`], four bytes to a channel.
+    pub(super) fn read_aov_bytes(&mut self, bytes: &[u8]) {
+        self.aovs = bytes
+            .chunks(4)
+            .map(|sum| f32::from_le_bytes(sum.try_into().unwrap()))
+            .collect();
+    }
+
+    /// An estimate of how far the pixel's luminance is from what infinitely many samples would
+    /// give: the standard error of the mean of its samples.
+    pub(super) fn standard_error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let n = self.samples as f32;
+        (self.squared_deviations / ((n - 1.0) * n)).max(0.0).sqrt()
+    }
+}
+
+/// The perceived brightness of a linear sRGB color.
+fn luminance(color: Color) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
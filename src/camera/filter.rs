@@ -0,0 +1,179 @@
+use std::f32::consts::{PI, TAU};
+
+/// How many bins each axis of a filter is tabulated into for importance sampling it.
+const FILTER_TABLE_SIZE: usize = 256;
+
+/// A pixel reconstruction filter, weighting samples by their distance from the center of the
+/// pixel.  Every filter is separable, and reaches `radius` pixels out along each axis.
+#[derive(Debug, Copy, Clone)]
+pub enum Filter {
+    /// Weighs every sample equally.  With a radius of a half, this averages the samples within
+    /// each pixel.
+    Box { radius: f32 },
+    /// Falls off linearly to zero at the radius.
+    Tent { radius: f32 },
+    /// A Gaussian with standard deviation `sigma`, shifted down to reach zero at the radius.
+    Gaussian { radius: f32, sigma: f32 },
+    /// The cubic filter from Mitchell and Netravali, "Reconstruction Filters in Computer
+    /// Graphics" (1988), where `b` and `c` trade blurring against ringing.  They recommend
+    /// `b = c = 1/3`.
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// The four term Blackman-Harris window, a smooth bump with very little energy in its tails.
+    BlackmanHarris { radius: f32 },
+    /// A sinc filter windowed by a wider sinc, which falls to zero after `tau` lobes.
+    Lanczos { radius: f32, tau: f32 },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// How far the filter reaches from the center of a pixel along each axis, in pixels.
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Self::Box { radius }
+            | Self::Tent { radius }
+            | Self::Gaussian { radius, .. }
+            | Self::Mitchell { radius, .. }
+            | Self::BlackmanHarris { radius }
+            | Self::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    /// Evaluates the filter along one axis, `x` pixels from the center.
+    fn evaluate(&self, x: f32) -> f32 {
+        let radius = self.radius();
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+
+        match *self {
+            Self::Box { .. } => 1.0,
+            Self::Tent { radius } => radius - x,
+            Self::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.0)
+            }
+            Self::Mitchell { radius, b, c } => {
+                let x = 2.0 * x / radius;
+                if x <= 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            Self::BlackmanHarris { radius } => {
+                let n = 0.5 * (x / radius + 1.0);
+                0.35875 - 0.48829 * (TAU * n).cos() + 0.14128 * (2.0 * TAU * n).cos()
+                    - 0.01168 * (3.0 * TAU * n).cos()
+            }
+            Self::Lanczos { tau, .. } => sinc(x) * sinc(x / tau),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Importance samples offsets from the center of a pixel in proportion to the magnitude of a
+/// filter, so that samples can be weighted by the filter without being shared between pixels.
+/// Offsets where the filter is negative get negative weights.
+#[derive(Debug, Clone)]
+pub(super) struct FilterSampler {
+    filter: Filter,
+    /// The filter's value at the center of each bin along an axis.
+    values: Vec<f32>,
+    /// The cumulative distribution of the filter's magnitude over the bins along an axis.
+    cdf: Vec<f32>,
+    /// The integral of the filter's magnitude along an axis.
+    magnitude_integral: f32,
+    /// The integral of the filter along an axis.
+    integral: f32,
+}
+
+impl FilterSampler {
+    pub(super) fn new(filter: Filter) -> Self {
+        let radius = filter.radius();
+        let bin_width = 2.0 * radius / FILTER_TABLE_SIZE as f32;
+        let values: Vec<f32> = (0..FILTER_TABLE_SIZE)
+            .map(|k| filter.evaluate(-radius + (k as f32 + 0.5) * bin_width))
+            .collect();
+
+        let mut cdf = Vec::with_capacity(FILTER_TABLE_SIZE + 1);
+        cdf.push(0.0);
+        for value in &values {
+            cdf.push(cdf.last().unwrap() + value.abs() * bin_width);
+        }
+        let magnitude_integral = *cdf.last().unwrap();
+        if magnitude_integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= magnitude_integral);
+        }
+
+        Self {
+            filter,
+            integral: values.iter().sum::<f32>() * bin_width,
+            values,
+            cdf,
+            magnitude_integral,
+        }
+    }
+
+    /// Samples an offset from the center of a pixel in pixels, given a pair of uniform values in
+    /// `[0, 1)`, along with the weight of a sample taken there.
+    pub(super) fn sample(&self, (u, v): (f32, f32)) -> ((f32, f32), f32) {
+        let (x, weight_x) = self.sample_axis(u);
+        let (y, weight_y) = self.sample_axis(v);
+        ((x, y), weight_x * weight_y)
+    }
+
+    fn sample_axis(&self, u: f32) -> (f32, f32) {
+        let radius = self.filter.radius();
+        if self.magnitude_integral <= 0.0 || self.integral == 0.0 {
+            return ((u - 0.5) * 2.0 * radius, 1.0);
+        }
+
+        // find the bin `u` falls in, then where within it.
+        let bin = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .clamp(1, FILTER_TABLE_SIZE)
+            - 1;
+        let (start, end) = (self.cdf[bin], self.cdf[bin + 1]);
+        let fraction = if end > start {
+            (u - start) / (end - start)
+        } else {
+            0.5
+        };
+
+        let bin_width = 2.0 * radius / FILTER_TABLE_SIZE as f32;
+        let x = -radius + (bin as f32 + fraction) * bin_width;
+
+        // weigh by the tabulated filter over the probability of sampling its bin, normalized so
+        // that the weights average to one.  using the table rather than evaluating the filter at
+        // `x` keeps every weight the same magnitude, so bins straddling a zero crossing can't
+        // blow up.
+        let value = self.values[bin];
+        let weight = if value == 0.0 {
+            0.0
+        } else {
+            value.signum() * self.magnitude_integral / self.integral
+        };
+        (x, weight)
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    sync::OnceLock,
+};
+
+use crate::{
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+/// How many rings the film is divided into when bounding the exit pupil.
+const PUPIL_BOUNDS_RINGS: usize = 64;
+/// How many rays are traced per ring when bounding the exit pupil, along each axis of a grid
+/// over the rear element.
+const PUPIL_BOUNDS_GRID: usize = 128;
+
+/// One surface in a lens system, as given by a row of its prescription.  All lengths are in
+/// millimeters.
+#[derive(Debug, Copy, Clone)]
+pub struct LensElement {
+    /// Radius of curvature of the surface, positive when its center lies behind it (towards the
+    /// film).  Zero marks the aperture stop.
+    pub radius: f32,
+    /// Distance along the axis to the next surface, or to the film for the last one.
+    pub thickness: f32,
+    /// Index of refraction of the material between this surface and the next, where `0` stands
+    /// for air.
+    pub ior: f32,
+    /// Diameter of the surface.
+    pub aperture: f32,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.radius == 0.0
+    }
+
+    fn ior(&self) -> f32 {
+        if self.ior == 0.0 {
+            1.0
+        } else {
+            self.ior
+        }
+    }
+}
+
+/// A camera lens made of a sequence of spherical elements, traced ray by ray to get its
+/// distortion, vignetting and focus breathing.
+///
+/// The lens works in its own space, measured in millimeters along the axis: the film sits at
+/// `z = 0` and the lens extends towards the scene, down `-z`.
+#[derive(Debug, Clone)]
+pub struct LensSystem {
+    /// The surfaces of the lens, from the front (facing the scene) to the back.
+    elements: Vec<LensElement>,
+    film_diagonal: f32,
+    units_per_meter: f32,
+    film_width: f32,
+    film_height: f32,
+    /// The exit pupil of the lens as it's focused, bounded the first time a ray needs it.
+    exit_pupil: OnceLock<ExitPupil>,
+}
+
+/// Where light from the scene leaves the rear element of a lens, for points across the film.
+#[derive(Debug, Clone)]
+struct ExitPupil {
+    /// Bounds of the exit pupil on the plane of the rear element for points on the film's `+x`
+    /// axis, by distance from the center of the film, as `(min_x, min_y, max_x, max_y)`.
+    bounds: Vec<(f32, f32, f32, f32)>,
+    /// Area of the exit pupil seen from the center of the film, which rays are weighted against
+    /// so the middle of the image keeps its brightness.
+    center_area: f32,
+}
+
+impl LensSystem {
+    /// Creates a lens system from its elements, listed from the front (facing the scene) to the
+    /// back.
+    pub fn new(elements: Vec<LensElement>) -> Self {
+        Self {
+            elements,
+            film_diagonal: 35.0,
+            units_per_meter: 1.0,
+            film_width: 0.0,
+            film_height: 0.0,
+            exit_pupil: OnceLock::new(),
+        }
+    }
+
+    /// Loads a lens prescription: a table with one row per surface, from the front of the lens to
+    /// the back, giving its radius of curvature, thickness, index of refraction and aperture
+    /// diameter, all in millimeters.  This is the format of pbrt's lens files: lines starting
+    /// with `#` are comments, and the aperture stop is the surface with a radius of `0`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let prescription = std::fs::read_to_string(path)?;
+        Self::from_prescription(&prescription)
+    }
+
+    fn from_prescription(prescription: &str) -> Result<Self> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+
+        let elements = prescription
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let values = line
+                    .split_whitespace()
+                    .map(|v| v.parse::<f32>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|e| invalid(format!("{e} in lens prescription row: {line}")))?;
+                match values[..] {
+                    [radius, thickness, ior, aperture] => Ok(LensElement {
+                        radius,
+                        thickness,
+                        ior,
+                        aperture,
+                    }),
+                    _ => Err(invalid(format!(
+                        "expected 4 values in lens prescription row: {line}"
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if elements.is_empty() {
+            return Err(invalid("lens prescription has no elements".to_string()));
+        }
+        Ok(Self::new(elements))
+    }
+
+    /// Sets the diagonal of the film, in millimeters.  Defaults to 35.
+    pub fn with_film_diagonal(mut self, diagonal: f32) -> Self {
+        self.film_diagonal = diagonal;
+        self
+    }
+
+    /// Stops the aperture down to `diameter` millimeters.  Has no effect if the lens's aperture
+    /// stop is already smaller than that.
+    pub fn with_aperture_diameter(mut self, diameter: f32) -> Self {
+        if let Some(stop) = self.elements.iter_mut().find(|e| e.is_stop()) {
+            stop.aperture = stop.aperture.min(diameter);
+        }
+        self
+    }
+
+    /// Sets how many scene units make up a meter.  Defaults to 1.
+    pub fn with_units_per_meter(mut self, units_per_meter: f32) -> Self {
+        self.units_per_meter = units_per_meter;
+        self
+    }
+
+    /// How many scene units make up one of the lens's millimeters.
+    pub(super) fn scale(&self) -> f32 {
+        1e-3 * self.units_per_meter
+    }
+
+    /// Readies the lens to render an image with the given aspect ratio, focused `focus_dist`
+    /// scene units away from the film.
+    pub(super) fn prepare(&mut self, aspect_ratio: f32, focus_dist: f32) {
+        self.film_height = self.film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        self.film_width = self.film_height * aspect_ratio;
+
+        self.focus(focus_dist / self.scale());
+        self.exit_pupil = OnceLock::new();
+    }
+
+    /// Moves the lens so that objects `distance` millimeters from the film are in focus, treating
+    /// it as a thick lens.
+    fn focus(&mut self, distance: f32) {
+        let Some((principal_plane, image_principal_plane, focal_length)) = self.thick_lens() else {
+            return;
+        };
+
+        // shift the lens by `delta` towards the scene, so that the object and image distances
+        // from the principal planes satisfy the lens equation.
+        let a = principal_plane + distance;
+        let b = -image_principal_plane;
+        let discriminant = (a + b) * (a + b - 4.0 * focal_length);
+        if discriminant < 0.0 {
+            return;
+        }
+        let delta = 0.5 * (a - b - discriminant.sqrt());
+
+        if let Some(last) = self.elements.last_mut() {
+            last.thickness = (last.thickness + delta).max(0.0);
+        }
+    }
+
+    /// Finds the principal planes on the scene's and on the film's side of the lens, and its
+    /// focal length, by tracing rays parallel to the axis through it.
+    fn thick_lens(&self) -> Option<(f32, f32, f32)> {
+        let x = 0.001 * self.film_diagonal;
+
+        // where a ray leaving the lens crosses the axis, and where it reaches the height it
+        // entered at.
+        let cardinal_points = |ray: &Ray| {
+            let (o, d) = (ray.origin(), ray.direction());
+            let focal_point = o.z() - o.x() / d.x() * d.z();
+            let principal_plane = o.z() + (x - o.x()) / d.x() * d.z();
+            (principal_plane, focal_point)
+        };
+
+        let from_scene = Ray::new(
+            Point3::new(x, 0.0, self.front_z() - 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let (image_principal_plane, image_focal_point) =
+            cardinal_points(&self.trace_from_scene(&from_scene)?);
+
+        let from_film = Ray::new(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let (principal_plane, _) = cardinal_points(&self.trace_from_film(&from_film)?);
+
+        Some((
+            principal_plane,
+            image_principal_plane,
+            image_focal_point - image_principal_plane,
+        ))
+    }
+
+    /// Position of the front element along the axis.
+    fn front_z(&self) -> f32 {
+        -self.elements.iter().map(|e| e.thickness).sum::<f32>()
+    }
+
+    /// Position of the rear element along the axis.
+    fn rear_z(&self) -> f32 {
+        -self.elements.last().map_or(0.0, |e| e.thickness)
+    }
+
+    fn rear_radius(&self) -> f32 {
+        self.elements.last().map_or(0.0, |e| 0.5 * e.aperture)
+    }
+
+    fn exit_pupil(&self) -> &ExitPupil {
+        self.exit_pupil.get_or_init(|| self.bound_exit_pupil())
+    }
+
+    /// Bounds the exit pupil, the part of the rear element that light from the scene reaches the
+    /// film through, for points at increasing distance from the center of the film.
+    fn bound_exit_pupil(&self) -> ExitPupil {
+        let rear_z = self.rear_z();
+        let extent = 1.5 * self.rear_radius();
+        let cell = 2.0 * extent / PUPIL_BOUNDS_GRID as f32;
+        let ring_width = 0.5 * self.film_diagonal / PUPIL_BOUNDS_RINGS as f32;
+
+        let grid = (0..PUPIL_BOUNDS_GRID).flat_map(|i| (0..PUPIL_BOUNDS_GRID).map(move |j| (i, j)));
+
+        let mut center_area = 0.0;
+        let bounds = (0..PUPIL_BOUNDS_RINGS)
+            .map(|ring| {
+                let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+                let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+                let mut passed = 0;
+
+                for (i, j) in grid.clone() {
+                    // spread the points on the film across the ring, in an order that has nothing
+                    // to do with the grid's, so that every render of the lens bounds it the same.
+                    let k = (i * PUPIL_BOUNDS_GRID + j) as u32;
+                    let jitter = (k.reverse_bits() >> 8) as f32 / (1 << 24) as f32;
+                    let film_x = (ring as f32 + jitter) * ring_width;
+                    let rear_x = -extent + (i as f32 + 0.5) * cell;
+                    let rear_y = -extent + (j as f32 + 0.5) * cell;
+
+                    let film_point = Point3::new(film_x, 0.0, 0.0);
+                    let ray =
+                        Ray::new(film_point, Point3::new(rear_x, rear_y, rear_z) - film_point);
+                    if self.trace_from_film(&ray).is_some() {
+                        min_x = min_x.min(rear_x);
+                        min_y = min_y.min(rear_y);
+                        max_x = max_x.max(rear_x);
+                        max_y = max_y.max(rear_y);
+                        passed += 1;
+                    }
+                }
+
+                if ring == 0 {
+                    center_area = passed as f32 * cell * cell;
+                }
+                if passed == 0 {
+                    (0.0, 0.0, 0.0, 0.0)
+                } else {
+                    // pad the bounds by a cell, since rays between grid points may still make it
+                    // through.
+                    (min_x - cell, min_y - cell, max_x + cell, max_y + cell)
+                }
+            })
+            .collect();
+
+        ExitPupil {
+            bounds,
+            center_area,
+        }
+    }
+
+    /// Samples a ray leaving the lens towards the scene for the image position `(s, t)`, where
+    /// both range from `0` to `1` from the top left corner, along with its weight.  `(u, v)` are
+    /// uniform values in `[0, 1)` choosing where the ray leaves the rear element.  Returns
+    /// `None` if the sampled ray is blocked inside the lens.
+    pub(super) fn sample_ray(&self, s: f32, t: f32, (u, v): (f32, f32)) -> Option<(Ray, f32)> {
+        // the lens flips the image, so the top left of the image falls on the bottom right of the
+        // film.
+        let film_point = Point3::new(
+            (0.5 - s) * self.film_width,
+            (t - 0.5) * self.film_height,
+            0.0,
+        );
+        let r = film_point.x().hypot(film_point.y());
+
+        // the corners of the image, and filter offsets past them, fall in the outermost ring.
+        let ring = ((r / (0.5 * self.film_diagonal)) * PUPIL_BOUNDS_RINGS as f32) as usize;
+        let ring = ring.min(PUPIL_BOUNDS_RINGS - 1);
+        let exit_pupil = self.exit_pupil();
+        let (min_x, min_y, max_x, max_y) = *exit_pupil.bounds.get(ring)?;
+        let area = (max_x - min_x) * (max_y - min_y);
+        if area <= 0.0 || exit_pupil.center_area <= 0.0 {
+            return None;
+        }
+
+        // the bounds are for points along the film's x axis, so rotate them to this point.
+        let x = min_x + u * (max_x - min_x);
+        let y = min_y + v * (max_y - min_y);
+        let (sin_phi, cos_phi) = if r > 0.0 {
+            (film_point.y() / r, film_point.x() / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear_point = Point3::new(
+            cos_phi * x - sin_phi * y,
+            sin_phi * x + cos_phi * y,
+            self.rear_z(),
+        );
+
+        let direction = (rear_point - film_point).normalize();
+        let ray = self.trace_from_film(&Ray::new(film_point, direction))?;
+
+        // light reaching the film falls off with the fourth power of the cosine of its angle.
+        let cos_theta = direction.z().abs();
+        let weight = cos_theta.powi(4) * area / exit_pupil.center_area;
+        Some((ray, weight))
+    }
+
+    /// Traces `ray` from the film out through the lens, returning the ray that leaves the front
+    /// element, or `None` if it's blocked along the way.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(ray.origin(), ray.direction());
+        let mut element_z = 0.0;
+
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            element_z -= element.thickness;
+            let eta_t = if i > 0 {
+                self.elements[i - 1].ior()
+            } else {
+                1.0
+            };
+            ray = self.refract_at(element, element_z, &ray, element.ior(), eta_t)?;
+        }
+
+        Some(ray)
+    }
+
+    /// Traces `ray` from the scene in through the lens, returning the ray that leaves the rear
+    /// element, or `None` if it's blocked along the way.
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(ray.origin(), ray.direction());
+        let mut element_z = self.front_z();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            let eta_i = if i > 0 {
+                self.elements[i - 1].ior()
+            } else {
+                1.0
+            };
+            ray = self.refract_at(element, element_z, &ray, eta_i, element.ior())?;
+            element_z += element.thickness;
+        }
+
+        Some(ray)
+    }
+
+    /// Passes `ray` through the surface of `element`, whose vertex lies at `element_z`, from a
+    /// medium with index of refraction `eta_i` into one with `eta_t`.
+    fn refract_at(
+        &self,
+        element: &LensElement,
+        element_z: f32,
+        ray: &Ray,
+        eta_i: f32,
+        eta_t: f32,
+    ) -> Option<Ray> {
+        let (origin, direction) = (ray.origin(), ray.direction().normalize());
+
+        let (t, normal) = if element.is_stop() {
+            if direction.z() == 0.0 {
+                return None;
+            }
+            ((element_z - origin.z()) / direction.z(), None)
+        } else {
+            let (t, normal) = intersect_spherical_element(
+                element.radius,
+                element_z + element.radius,
+                &origin,
+                &direction,
+            )?;
+            (t, Some(normal))
+        };
+        if t < 0.0 {
+            return None;
+        }
+
+        let hit = origin + t * direction;
+        let aperture_radius = 0.5 * element.aperture;
+        if hit.x() * hit.x() + hit.y() * hit.y() > aperture_radius * aperture_radius {
+            return None;
+        }
+
+        let Some(normal) = normal else {
+            return Some(Ray::new(hit, direction));
+        };
+
+        // light entirely reflects off the surface past the critical angle.
+        let eta = eta_i / eta_t;
+        let cos_theta = (-direction).dot(&normal).min(1.0);
+        if eta * eta * (1.0 - cos_theta * cos_theta) > 1.0 {
+            return None;
+        }
+
+        Some(Ray::new(hit, direction.refract(normal, eta)))
+    }
+}
+
+/// Intersects a ray with the sphere of the given `radius` centered on the axis at `center_z`,
+/// choosing the side of the sphere that the lens surface lies on.  Returns the distance along the
+/// ray and the surface normal, facing against the ray.
+fn intersect_spherical_element(
+    radius: f32,
+    center_z: f32,
+    origin: &Point3,
+    direction: &Vec3,
+) -> Option<(f32, Vec3)> {
+    let o = *origin - Point3::new(0.0, 0.0, center_z);
+    let a = direction.len_squared();
+    let half_b = direction.dot(&o);
+    let c = o.len_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((-half_b - root) / a, (-half_b + root) / a);
+
+    // the surface is the near side of the sphere for rays heading towards the film through a
+    // convex surface, or towards the scene through a concave one.
+    let use_closer = (direction.z() > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t0 } else { t1 };
+    if t < 0.0 {
+        return None;
+    }
+
+    let normal = (o + t * *direction).normalize();
+    let normal = if normal.dot(direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    Some((t, normal))
+}
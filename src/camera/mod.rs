@@ -0,0 +1,1837 @@
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::{BufWriter, Write},
+    num::{NonZeroU32, NonZeroU64},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use indicatif::ProgressStyle;
+use rayon::prelude::*;
+
+mod adaptive;
+mod aov;
+mod aperture;
+mod checkpoint;
+mod contact_sheet;
+mod crop;
+mod denoise;
+mod distributed;
+mod film;
+mod filter;
+mod lens;
+mod physical;
+mod progressive;
+mod projection;
+mod stereo;
+mod tiles;
+mod tone_map;
+pub use adaptive::AdaptiveSampling;
+pub use aov::{Aov, AovFormat, Aovs};
+use aov::{AovSample, Layer, Surface};
+pub use aperture::{Aperture, ApertureMask, ApertureShape};
+pub use checkpoint::Checkpoint;
+use checkpoint::CheckpointData;
+pub use contact_sheet::ContactSheet;
+pub use crop::{Crop, CropWindow};
+pub use denoise::Denoise;
+use distributed::Work;
+pub use distributed::{Coordinator, Worker};
+use film::Pixel;
+pub use filter::Filter;
+use filter::FilterSampler;
+pub use lens::{LensElement, LensSystem};
+pub use physical::PhysicalCamera;
+pub use progressive::Progressive;
+pub use projection::{FisheyeMapping, Projection};
+pub use stereo::{Eye, Stereo, StereoLayout};
+pub use tiles::{PpmTileOutput, Tile, TileOrder, TileOutput, Tiles};
+use tone_map::Display;
+pub use tone_map::{OutputTransform, ToneMap};
+
+use crate::{
+    geometry::{HitRecord, Hittable},
+    light::{Light, LightList},
+    material::MediumEvent,
+    ray::Ray,
+    sampler::{Sampler, SamplerKind},
+    sky::Background,
+    spectrum::{Radiance, SampledSpectrum, SampledWavelengths},
+    util::{suffixed_path, write_atomically},
+    vec3::{Color, Point3, Vec3},
+};
+
+/// How many samples to take for every pixel in each pass of a render that needs to stop between
+/// passes every so often, to save checkpoints or to check how much time it has left.
+const PASS_SAMPLES: u32 = 4;
+
+/// A camera to render a scene with.
+#[derive(Debug)]
+pub struct Camera {
+    samples_per_pixel: NonZeroU32,
+    image_width: NonZeroU64,
+    image_height: NonZeroU64,
+    /// The pixels of the image rendered, which are all of them unless it's cropped.
+    crop: Tile,
+    /// Whether to write cropped images at full size, black outside the crop.
+    crop_full_frame: bool,
+    center: Point3,
+    pixel00_loc: Point3,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
+    max_depth: NonZeroU32,
+    defocus_angle: f32,
+    focus_dist: f32,
+    /// How the light reaching the camera is exposed and turned into the colors of the image.
+    display: Display,
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
+    aperture: Aperture,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    projection: Projection,
+    lens: Option<LensSystem>,
+    stereo: Option<Stereo>,
+    sampler: SamplerKind,
+    filter: FilterSampler,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    sample_heatmap: Option<PathBuf>,
+    progressive: Option<Progressive>,
+    checkpoint: Option<Checkpoint>,
+    time_limit: Option<Duration>,
+    /// The tiles to render the image in, in order, or none to render pixel by pixel.
+    tiles: Vec<Tile>,
+    tile_output: Option<Arc<dyn TileOutput>>,
+    aovs: Option<Aovs>,
+    /// The buffers gathered for each pixel: those written, and those guiding the denoiser.
+    gathered_aovs: Vec<Aov>,
+    denoise: Option<Denoise>,
+    /// A hash of the settings that determine what the image looks like, for checking that a
+    /// checkpoint belongs to this camera.
+    settings_hash: u64,
+    seed: u64,
+    spectral: bool,
+    background: Background,
+}
+
+impl Camera {
+    /// Renders a PPM image of `world`, lit by `lights`, to `output`.  Stereo cameras render both
+    /// eyes into the one image, arranged according to their layout.
+    pub fn render_to_io<Output, World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        output: &mut Output,
+    ) -> std::io::Result<()>
+    where
+        Output: std::io::Write,
+        World: Hittable + std::marker::Sync,
+    {
+        let eyes = self.render_eyes(world, lights, &self.eyes(), None)?;
+        self.write_image(output, &eyes, Some(lights))
+    }
+
+    /// Renders the image [`Camera::render_to_io`] would write, returning its width, height and
+    /// the colors of its pixels, as they're displayed, in row-major order.
+    pub fn render_colors<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+    ) -> std::io::Result<(u64, u64, Vec<Color>)>
+    where
+        World: Hittable + std::marker::Sync,
+    {
+        let eyes = self.render_eyes(world, lights, &self.eyes(), None)?;
+        let image = self.finish_image(&eyes, Some(lights.iter().count()));
+        let colors = image
+            .colors
+            .iter()
+            .map(|&color| self.display.apply(color))
+            .collect();
+        Ok((image.width, image.height, colors))
+    }
+
+    /// Renders the same image as [`Camera::render_to_io`], but by handing out its tiles to the
+    /// workers that connect to `coordinator`, which have to have set up the same camera and
+    /// scene.  Tiles of workers that drop out are handed to others.
+    pub fn render_distributed_to_io<Output, World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        coordinator: &Coordinator,
+        output: &mut Output,
+    ) -> std::io::Result<()>
+    where
+        Output: std::io::Write,
+        World: Hittable + std::marker::Sync,
+    {
+        let eyes = self.eyes();
+        let tiles = match &self.tiles[..] {
+            [] => Tiles::default().layout(self.crop.width, self.crop.height),
+            tiles => tiles.to_vec(),
+        };
+        let work = Work::new(eyes.len(), self.num_pixels(), &tiles);
+
+        let progress_bar = progress_bar((self.num_pixels() * eyes.len()) as u64);
+        coordinator.run(
+            self.distributed_hash(world, lights),
+            self.crop.width,
+            &work,
+            &progress_bar,
+        )?;
+        progress_bar.finish_and_clear();
+
+        self.write_image(output, &work.into_eyes(), None)
+    }
+
+    /// Renders the tiles `worker`'s coordinator asks for until it hangs up.  The camera and scene
+    /// have to be the same as the coordinator's.
+    pub fn work<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        mut worker: Worker,
+    ) -> std::io::Result<()>
+    where
+        World: Hittable + std::marker::Sync,
+    {
+        worker.send_scene_hash(self.distributed_hash(world, lights))?;
+
+        let eyes = self.eyes();
+        while let Some((k, tile)) = worker.next_tile()? {
+            if k >= eyes.len()
+                || tile.x + tile.width > self.crop.width
+                || tile.y + tile.height > self.crop.height
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "asked for a tile outside the image",
+                ));
+            }
+
+            let mut pixels = vec![Pixel::default(); (tile.width * tile.height) as usize];
+            let indices: Vec<usize> = tile.pixel_indices(self.crop.width).collect();
+            pixels
+                .par_iter_mut()
+                .zip(indices)
+                .for_each(|(pixel, index)| {
+                    while !pixel.done {
+                        self.sample_pixel(world, lights, eyes[k], index, pixel);
+                    }
+                });
+            worker.send_pixels(&pixels)?;
+        }
+
+        eprintln!("Done!");
+        Ok(())
+    }
+
+    /// The eyes to render, or just the center of the camera without stereo.
+    fn eyes(&self) -> Vec<Option<Eye>> {
+        match self.stereo {
+            None => vec![None],
+            Some(_) => vec![Some(Eye::Left), Some(Eye::Right)],
+        }
+    }
+
+    /// Writes a PPM image of the pixels of `eyes` to `output`, along with the heatmap of their
+    /// samples if asked for, and their AOVs if they were gathered in a scene with `lights`.
+    fn write_image<Output: std::io::Write>(
+        &self,
+        output: &mut Output,
+        eyes: &[Vec<Pixel>],
+        lights: Option<&LightList>,
+    ) -> std::io::Result<()> {
+        let lights = lights.map(|lights| lights.iter().count());
+        let FinishedImage {
+            width,
+            height,
+            pixels,
+            colors,
+            layers,
+        } = self.finish_image(eyes, lights);
+        self.write_ppm(output, width, height, colors.iter().copied())?;
+        if let Some(path) = &self.sample_heatmap {
+            self.write_heatmap(path, width, height, &pixels)?;
+        }
+        if let (Some(aovs), Some(_)) = (&self.aovs, lights) {
+            let colors: Vec<Color> = colors
+                .iter()
+                .map(|&color| color * self.display.exposure)
+                .collect();
+            aovs.write(&aovs.path, width, height, &layers, &colors, &self.display)?;
+        }
+
+        eprintln!("Done!");
+        Ok(())
+    }
+
+    /// A hash of the scene and everything about the camera, for checking that workers render
+    /// exactly what their coordinator would.
+    fn distributed_hash<World: Hittable>(&self, world: &World, lights: &LightList) -> u64 {
+        checkpoint::hash(&[
+            format!("{self:?}").as_bytes(),
+            format!("{world:?}").as_bytes(),
+            format!("{lights:?}").as_bytes(),
+        ])
+    }
+
+    /// Renders a PPM image of what a single eye of a stereo camera sees.  Cameras without stereo
+    /// settings render the same image for both eyes.
+    pub fn render_eye_to_io<Output, World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        eye: Eye,
+        output: &mut Output,
+    ) -> std::io::Result<()>
+    where
+        Output: std::io::Write,
+        World: Hittable + std::marker::Sync,
+    {
+        let eyes = self.render_eyes(world, lights, &[Some(eye)], Some(eye))?;
+        let FinishedImage {
+            width,
+            height,
+            pixels,
+            colors,
+            layers,
+        } = self.finish_image(&eyes, Some(lights.iter().count()));
+        self.write_ppm(output, width, height, colors.iter().copied())?;
+        if let Some(path) = &self.sample_heatmap {
+            self.write_heatmap(&eye_path(path, eye), width, height, &pixels)?;
+        }
+        if let Some(aovs) = &self.aovs {
+            let colors: Vec<Color> = colors
+                .iter()
+                .map(|&color| color * self.display.exposure)
+                .collect();
+            aovs.write(
+                &eye_path(&aovs.path, eye),
+                width,
+                height,
+                &layers,
+                &colors,
+                &self.display,
+            )?;
+        }
+
+        eprintln!("Done!");
+        Ok(())
+    }
+
+    /// The image made up of the pixels of each eye rendered, with its colors denoised if asked
+    /// for and the values of the AOVs gathered in a scene with `lights` lights.  Pixels rendered
+    /// by workers come without their AOVs, and so without `lights`, and aren't denoised.
+    fn finish_image(&self, eyes: &[Vec<Pixel>], lights: Option<usize>) -> FinishedImage {
+        let frames: Vec<Vec<Pixel>> = eyes.iter().map(|eye| self.frame(eye)).collect();
+        // each eye is denoised on its own, so that no pixel is averaged with the other eye's
+        // across the seam between them.
+        let colors = frames
+            .iter()
+            .map(|frame| self.frame_colors(frame, lights))
+            .collect();
+        let (_, _, colors) = self.arrange_frames(colors);
+        let (width, height, pixels) = self.arrange_frames(frames);
+        let lights = lights.unwrap_or_default();
+        let layers = aov::layers(&self.gathered_aovs, &pixels, lights, self.display.exposure);
+        FinishedImage {
+            width,
+            height,
+            pixels,
+            colors,
+            layers,
+        }
+    }
+
+    /// The colors of the image of an eye made up of `pixels`, denoised if asked for and the
+    /// pixels carry the guides.
+    fn frame_colors(&self, pixels: &[Pixel], lights: Option<usize>) -> Vec<Color> {
+        let colors: Vec<Color> = pixels.iter().map(Pixel::color).collect();
+        let (Some(denoise), Some(lights)) = (&self.denoise, lights) else {
+            return colors;
+        };
+
+        let (width, height) = self.frame_size();
+        let layers = aov::layers(&self.gathered_aovs, pixels, lights, self.display.exposure);
+        let variances: Vec<f32> = pixels
+            .iter()
+            .map(|pixel| pixel.standard_error().powi(2))
+            .collect();
+        denoise.apply(width, height, &colors, &variances, &layers)
+    }
+
+    /// Arranges the pixels of each eye rendered into a single image, returning its width, height
+    /// and pixels in row-major order.
+    fn arrange<T: Clone + Default>(&self, eyes: &[Vec<T>]) -> (u64, u64, Vec<T>) {
+        self.arrange_frames(eyes.iter().map(|eye| self.frame(eye)).collect())
+    }
+
+    /// Like [`Camera::arrange`], for the images of each eye already placed in their frames.
+    fn arrange_frames<T: Clone>(&self, eyes: Vec<Vec<T>>) -> (u64, u64, Vec<T>) {
+        let (width, height) = self.frame_size();
+        match (&eyes[..], self.stereo) {
+            ([left, right], Some(stereo)) => match stereo.layout {
+                StereoLayout::SideBySide => {
+                    let rows = left
+                        .chunks(width as usize)
+                        .zip(right.chunks(width as usize))
+                        .flat_map(|(left, right)| left.iter().chain(right.iter()).cloned());
+                    (2 * width, height, rows.collect())
+                }
+                StereoLayout::TopBottom => (
+                    width,
+                    2 * height,
+                    left.iter().chain(right.iter()).cloned().collect(),
+                ),
+            },
+            _ => (width, height, eyes.concat()),
+        }
+    }
+
+    /// The width and height of the image of each eye written, which is just the crop unless it's
+    /// placed in the full frame.
+    fn frame_size(&self) -> (u64, u64) {
+        if self.crop_full_frame {
+            (self.image_width.into(), self.image_height.into())
+        } else {
+            (self.crop.width, self.crop.height)
+        }
+    }
+
+    /// The pixels of the image of an eye written, given those rendered, with the crop placed in
+    /// the full frame if need be.
+    fn frame<T: Clone + Default>(&self, pixels: &[T]) -> Vec<T> {
+        if !self.crop_full_frame {
+            return pixels.to_vec();
+        }
+
+        let width = u64::from(self.image_width);
+        let mut frame =
+            vec![T::default(); self.image_width.get() as usize * self.image_height.get() as usize];
+        for (index, pixel) in self.crop.pixel_indices(width).zip(pixels) {
+            frame[index] = pixel.clone();
+        }
+        frame
+    }
+
+    /// Renders the images seen by each of `eyes` (or the center of the camera, for `None`) one
+    /// after another, returning the samples taken for each of their pixels in row-major order.
+    ///
+    /// Progressive renders write snapshots of all the eyes every so often, with those not yet
+    /// started on left black, and checkpointed renders save the samples taken so far.  When a
+    /// single eye is rendered to a file of its own, `file_eye` names it, and its name is added to
+    /// those of the snapshots and checkpoints too.
+    fn render_eyes<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        eyes: &[Option<Eye>],
+        file_eye: Option<Eye>,
+    ) -> std::io::Result<Vec<Vec<Pixel>>>
+    where
+        World: Hittable + std::marker::Sync,
+    {
+        let file_path = |path: &Path| match file_eye {
+            Some(eye) => eye_path(path, eye),
+            None => path.to_path_buf(),
+        };
+        let snapshot_path = self
+            .progressive
+            .as_ref()
+            .map(|p| file_path(&p.snapshot_path));
+        let checkpoint_path = self.checkpoint.as_ref().map(|c| file_path(&c.path));
+
+        let mut checkpoint = CheckpointData {
+            settings_hash: checkpoint::hash(&[
+                &self.settings_hash.to_le_bytes(),
+                format!("{world:?}").as_bytes(),
+                format!("{lights:?}").as_bytes(),
+                // the AOV sums saved only make sense for the same buffers.
+                format!("{:?}", self.gathered_aovs).as_bytes(),
+            ]),
+            seed: self.seed,
+            eyes: vec![vec![Pixel::default(); self.num_pixels()]; eyes.len()],
+        };
+        if let (Some(true), Some(path)) =
+            (self.checkpoint.as_ref().map(|c| c.resume), &checkpoint_path)
+        {
+            let resumed = CheckpointData::read(path, checkpoint.settings_hash, self.seed)?;
+            if resumed.eyes.len() != eyes.len()
+                || resumed
+                    .eyes
+                    .iter()
+                    .any(|eye| eye.len() != self.num_pixels())
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} is a checkpoint of a different image", path.display()),
+                ));
+            }
+            checkpoint = resumed;
+            for pixel in checkpoint.eyes.iter_mut().flatten() {
+                pixel.done = self.is_done(pixel);
+            }
+        }
+
+        // finished tiles go where the eye's image goes once the eyes are arranged into one.
+        let (width, height) = self.frame_size();
+        let (x, y) = match self.crop_full_frame {
+            true => (self.crop.x, self.crop.y),
+            false => (0, 0),
+        };
+        let layout = self
+            .stereo
+            .filter(|_| eyes.len() == 2)
+            .map(|stereo| stereo.layout);
+        if let Some(tile_output) = &self.tile_output {
+            match layout {
+                Some(StereoLayout::SideBySide) => tile_output.begin(2 * width, height)?,
+                Some(StereoLayout::TopBottom) => tile_output.begin(width, 2 * height)?,
+                None => tile_output.begin(width, height)?,
+            }
+        }
+
+        let start = Instant::now();
+        let mut last_snapshot = start;
+        let mut last_checkpoint = start;
+
+        for (k, &eye) in eyes.iter().enumerate() {
+            // each eye gets an equal share of the time.
+            let deadline = self
+                .time_limit
+                .map(|limit| start + limit.mul_f64((k + 1) as f64 / eyes.len() as f64));
+
+            let progress_bar = progress_bar(self.num_pixels() as u64);
+            progress_bar.inc(checkpoint.eyes[k].iter().filter(|p| p.done).count() as u64);
+
+            let pass = EyePass {
+                eye,
+                deadline,
+                progress_bar: &progress_bar,
+                tile_origin: match layout {
+                    Some(StereoLayout::SideBySide) => (x + k as u64 * width, y),
+                    Some(StereoLayout::TopBottom) => (x, y + k as u64 * height),
+                    None => (x, y),
+                },
+            };
+            // tiles resumed from a checkpoint already finished won't be finished again.
+            self.write_tiles(&checkpoint.eyes[k], &pass, |finished| finished)?;
+
+            // however short the time limit, every pixel gets a sample, rather than being left black.
+            while checkpoint.eyes[k].iter().any(|pixel| !pixel.done)
+                && (deadline.is_none_or(|deadline| Instant::now() < deadline)
+                    || checkpoint.eyes[k].iter().any(|pixel| pixel.samples() == 0))
+            {
+                self.render_pass(world, lights, &mut checkpoint.eyes[k], &pass)?;
+
+                // with a time limit, how far along the render is depends on the time left.
+                if let (Some(deadline), Some(limit)) = (deadline, self.time_limit) {
+                    let share = limit.div_f64(eyes.len() as f64);
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    let fraction = 1.0 - left.as_secs_f64() / share.as_secs_f64();
+                    let position = (fraction * self.num_pixels() as f64) as u64;
+                    progress_bar.set_position(progress_bar.position().max(position));
+                }
+
+                if let (Some(progressive), Some(path)) = (&self.progressive, &snapshot_path) {
+                    if last_snapshot.elapsed() >= progressive.snapshot_interval {
+                        self.write_snapshot(path, &checkpoint.eyes)?;
+                        last_snapshot = Instant::now();
+                    }
+                }
+                if let (Some(settings), Some(path)) = (&self.checkpoint, &checkpoint_path) {
+                    if last_checkpoint.elapsed() >= settings.interval {
+                        checkpoint.write(path)?;
+                        last_checkpoint = Instant::now();
+                    }
+                }
+            }
+
+            // tiles cut short by the time limit are written as they are.
+            self.write_tiles(&checkpoint.eyes[k], &pass, |finished| !finished)?;
+            progress_bar.finish_and_clear();
+        }
+
+        if let Some(path) = &checkpoint_path {
+            checkpoint.write(path)?;
+        }
+
+        Ok(checkpoint.eyes)
+    }
+
+    /// How many pixels of the image are rendered.
+    fn num_pixels(&self) -> usize {
+        (self.crop.width * self.crop.height) as usize
+    }
+
+    /// How many samples to take for every pixel in each pass over the image.  Unless there's a
+    /// reason to stop between passes, every pixel gets all its samples in a single pass.
+    fn pass_samples(&self) -> u32 {
+        if let Some(progressive) = &self.progressive {
+            progressive.samples_per_pass
+        } else if let Some(adaptive) = self.adaptive_sampling {
+            adaptive.min_samples_per_pixel
+        } else if self.checkpoint.is_some() || self.time_limit.is_some() {
+            PASS_SAMPLES
+        } else {
+            self.samples_per_pixel.into()
+        }
+    }
+
+    /// Whether `pixel` has all the samples it needs.  Without adaptive sampling, renders with a
+    /// time limit keep sampling every pixel until the time runs out.
+    fn is_done(&self, pixel: &Pixel) -> bool {
+        match (self.adaptive_sampling, self.time_limit) {
+            (Some(adaptive), _) => adaptive.is_converged(pixel, self.display.exposure),
+            (None, Some(_)) => false,
+            (None, None) => pixel.samples() >= self.samples_per_pixel.into(),
+        }
+    }
+
+    /// Takes a pass of samples for each pixel that isn't done yet, marking those that need no
+    /// more as done.  Pixels not yet started on when the deadline passes are left as they are,
+    /// unless they have no samples at all.
+    fn render_pass<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        pixels: &mut [Pixel],
+        pass: &EyePass,
+    ) -> std::io::Result<()>
+    where
+        World: Hittable + std::marker::Sync,
+    {
+        if !self.tiles.is_empty() {
+            return self.render_tiles(world, lights, pixels, pass);
+        }
+
+        pixels
+            .par_iter_mut()
+            .enumerate()
+            .filter(|(_, pixel)| !pixel.done)
+            .for_each(|(index, pixel)| {
+                if pass.is_over() && pixel.samples() > 0 {
+                    return;
+                }
+
+                self.sample_pixel(world, lights, pass.eye, index, pixel);
+                if pixel.done {
+                    pass.progress_bar.inc(1);
+                }
+            });
+        Ok(())
+    }
+
+    /// Takes a pass of samples for each pixel that isn't done yet a tile at a time, with each
+    /// thread taking on the next tile in order as soon as it's finished with the last.  Tiles
+    /// finished in this pass are written to the tile output.
+    fn render_tiles<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        pixels: &mut [Pixel],
+        pass: &EyePass,
+    ) -> std::io::Result<()>
+    where
+        World: Hittable + std::marker::Sync,
+    {
+        let width = self.crop.width;
+        let tiles: Vec<Mutex<Vec<Pixel>>> = self
+            .tiles
+            .iter()
+            .map(|tile| {
+                Mutex::new(
+                    tile.pixel_indices(width)
+                        .map(|k| pixels[k].clone())
+                        .collect(),
+                )
+            })
+            .collect();
+
+        let next = AtomicUsize::new(0);
+        let result = (0..rayon::current_num_threads())
+            .into_par_iter()
+            .try_for_each(|_| loop {
+                let k = next.fetch_add(1, Ordering::Relaxed);
+                let Some(tile) = self.tiles.get(k) else {
+                    return Ok(());
+                };
+                let mut tile_pixels = tiles[k].lock().unwrap();
+                let done = tile_pixels.iter().filter(|pixel| pixel.done).count();
+                if done == tile_pixels.len() {
+                    continue;
+                }
+                for (index, pixel) in tile.pixel_indices(width).zip(tile_pixels.iter_mut()) {
+                    if !pixel.done && (pixel.samples() == 0 || !pass.is_over()) {
+                        self.sample_pixel(world, lights, pass.eye, index, pixel);
+                    }
+                }
+
+                let now_done = tile_pixels.iter().filter(|pixel| pixel.done).count();
+                pass.progress_bar.inc((now_done - done) as u64);
+                if let (Some(tile_output), true) =
+                    (&self.tile_output, now_done == tile_pixels.len())
+                {
+                    self.write_tile(&**tile_output, tile, &tile_pixels, pass)?;
+                }
+            });
+
+        for (tile, tile_pixels) in self.tiles.iter().zip(tiles) {
+            for (index, pixel) in tile
+                .pixel_indices(width)
+                .zip(tile_pixels.into_inner().unwrap())
+            {
+                pixels[index] = pixel;
+            }
+        }
+        result
+    }
+
+    /// Takes a pass of samples for the pixel at `index`, as seen by `eye`, and marks it done if
+    /// it needs no more.
+    fn sample_pixel<World>(
+        &self,
+        world: &World,
+        lights: &LightList,
+        eye: Option<Eye>,
+        index: usize,
+        pixel: &mut Pixel,
+    ) where
+        World: Hittable + std::marker::Sync,
+    {
+        let i = self.crop.x + index as u64 % self.crop.width;
+        let j = self.crop.y + index as u64 / self.crop.width;
+
+        // passes stop short at the samples per pixel, which is the most adaptive sampling takes,
+        // unless it's up to a time limit alone how many samples pixels get.
+        let samples = match (self.adaptive_sampling, self.time_limit) {
+            (None, Some(_)) => self.pass_samples(),
+            _ => self
+                .pass_samples()
+                .min(u32::from(self.samples_per_pixel).saturating_sub(pixel.samples())),
+        };
+
+        let mut sampler = self
+            .sampler
+            .create(self.samples_per_pixel.into(), self.seed);
+        for _ in 0..samples {
+            sampler.start_pixel_sample(i, j, pixel.samples());
+            let (offset, filter_weight) = self.filter.sample(sampler.get_2d());
+            let ray = self.get_ray(i, j, offset, eye, &mut *sampler);
+            let radiance = match ray {
+                _ if !self.gathered_aovs.is_empty() => {
+                    let (radiance, sample) = match ray {
+                        Some((ray, weight)) => {
+                            let (color, sample) =
+                                self.sample_color_aovs(&ray, world, lights, &mut *sampler);
+                            let sample = sample.map(|color| filter_weight * weight * color);
+                            (weight * color, sample)
+                        }
+                        None => Default::default(),
+                    };
+                    let first = pixel.samples() == 0;
+                    let buffers = &self.gathered_aovs;
+                    aov::add_sample(buffers, &mut pixel.aovs, &sample, filter_weight, first);
+                    radiance
+                }
+                Some((ray, weight)) => {
+                    weight * self.sample_color(&ray, world, lights, &mut *sampler)
+                }
+                None => Color::default(),
+            };
+            pixel.add_sample(radiance, filter_weight);
+        }
+
+        pixel.done = self.is_done(pixel);
+    }
+
+    /// Writes the tiles of `pixels` for which `filter` returns true, given whether all of the
+    /// tile's pixels are done, to the tile output.
+    fn write_tiles(
+        &self,
+        pixels: &[Pixel],
+        pass: &EyePass,
+        filter: impl Fn(bool) -> bool,
+    ) -> std::io::Result<()> {
+        let Some(tile_output) = &self.tile_output else {
+            return Ok(());
+        };
+
+        let width = self.crop.width;
+        for tile in &self.tiles {
+            let tile_pixels: Vec<Pixel> = tile
+                .pixel_indices(width)
+                .map(|k| pixels[k].clone())
+                .collect();
+            if filter(tile_pixels.iter().all(|pixel| pixel.done)) {
+                self.write_tile(&**tile_output, tile, &tile_pixels, pass)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the colors of the pixels of `tile` to `tile_output`, moved to where the eye's image
+    /// goes.
+    fn write_tile(
+        &self,
+        tile_output: &dyn TileOutput,
+        tile: &Tile,
+        pixels: &[Pixel],
+        pass: &EyePass,
+    ) -> std::io::Result<()> {
+        let colors: Vec<[u8; 3]> = pixels
+            .iter()
+            .map(|pixel| self.display.apply(pixel.color()).to_rgb8(NonZeroU32::MIN))
+            .collect();
+        let (x, y) = pass.tile_origin;
+        let tile = Tile {
+            x: tile.x + x,
+            y: tile.y + y,
+            ..*tile
+        };
+        tile_output.write_tile(&tile, &colors)
+    }
+
+    /// Writes the colors of a `width` by `height` image to `output` as a PPM.
+    fn write_ppm<Output: std::io::Write>(
+        &self,
+        output: &mut Output,
+        width: u64,
+        height: u64,
+        pixels: impl Iterator<Item = Color>,
+    ) -> std::io::Result<()> {
+        write!(output, "P3\n{} {}\n255\n", width, height)?;
+
+        for color in pixels {
+            self.display
+                .apply(color)
+                .write_ppm(output, NonZeroU32::MIN)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the image at `path` with the colors of `eyes` so far.
+    fn write_snapshot(&self, path: &Path, eyes: &[Vec<Pixel>]) -> std::io::Result<()> {
+        let (width, height, pixels) = self.arrange(eyes);
+        let mut contents = Vec::new();
+        self.write_ppm(
+            &mut contents,
+            width,
+            height,
+            pixels.iter().map(Pixel::color),
+        )?;
+        write_atomically(path, &contents)
+    }
+
+    /// Writes a heatmap of how many samples each of `pixels` got to the file at `path`.
+    fn write_heatmap(
+        &self,
+        path: &Path,
+        width: u64,
+        height: u64,
+        pixels: &[Pixel],
+    ) -> std::io::Result<()> {
+        let (min, max) = match self.adaptive_sampling {
+            Some(adaptive) => (
+                adaptive.min_samples_per_pixel,
+                adaptive.max_samples_per_pixel,
+            ),
+            None => (0, self.samples_per_pixel.into()),
+        };
+
+        let mut output = BufWriter::new(File::create(path)?);
+        adaptive::write_heatmap(
+            &mut output,
+            width,
+            height,
+            pixels.iter().map(Pixel::samples),
+            min,
+            max,
+        )?;
+        output.flush()
+    }
+
+    /// Samples a ray through the point `offset` pixels from the center of the pixel at (i, j),
+    /// as seen by `eye`, or the center of the camera if `eye` is `None`, along with the weight of
+    /// its contribution to the pixel.  Returns `None` if the pixel lies outside the area the
+    /// camera's projection covers, or the ray is blocked inside the lens.
+    fn get_ray(
+        &self,
+        i: u64,
+        j: u64,
+        offset: (f32, f32),
+        eye: Option<Eye>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, f32)> {
+        let to_world = |v: Vec3| v.x() * self.u + v.y() * self.v + v.z() * self.w;
+
+        // a lens system takes the place of the projection, with the film at the camera's center.
+        if let Some(lens) = &self.lens {
+            let (s, t) = self.image_position(i, j, offset);
+            let (ray, weight) = lens.sample_ray(s, t, sampler.get_2d())?;
+            return Some((
+                Ray::new(
+                    self.center + lens.scale() * to_world(ray.origin()),
+                    to_world(ray.direction()),
+                ),
+                weight,
+            ));
+        }
+
+        let (lens_center, direction) = match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                let pixel_center = self.pixel00_loc
+                    + (i as f32 * self.pixel_delta_u)
+                    + (j as f32 * self.pixel_delta_v);
+                let pixel_sample = pixel_center + self.pixel_offset(offset);
+
+                // orthographic rays all run parallel to the view direction, so each starts from its
+                // own point on the plane of the camera.
+                let center = match self.projection {
+                    Projection::Orthographic { .. } => {
+                        pixel_sample - (pixel_sample - self.center).dot(&self.w) * self.w
+                    }
+                    _ => self.center,
+                };
+
+                let (lens_center, focus_point) = match (eye, self.stereo) {
+                    (Some(eye), Some(stereo)) => {
+                        // aim each eye at the point this pixel sees on the plane of convergence,
+                        // then find where that ray crosses the plane of focus.
+                        let convergence = stereo.convergence_distance;
+                        let offset = stereo.eye_offset(eye, &-self.w, false).x() * self.u;
+                        let lens_center = center + offset;
+                        let convergence_point =
+                            center + (pixel_sample - center) * (convergence / self.focus_dist);
+                        let focus_point = lens_center
+                            + (convergence_point - lens_center) * (self.focus_dist / convergence);
+                        (lens_center, focus_point)
+                    }
+                    _ => (center, pixel_sample),
+                };
+
+                let ray_origin = if self.defocus_angle <= 0.0 {
+                    lens_center
+                } else {
+                    lens_center + self.defocus_disk_sample(i, j, sampler)
+                };
+                return Some((Ray::new(ray_origin, focus_point - ray_origin), 1.0));
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let (s, t) = self.image_position(i, j, offset);
+                let aspect_ratio =
+                    u64::from(self.image_width) as f32 / u64::from(self.image_height) as f32;
+                (
+                    self.center,
+                    projection::fisheye_direction(s, t, aspect_ratio, fov, mapping)?,
+                )
+            }
+            Projection::Equirectangular => {
+                let (s, t) = self.image_position(i, j, offset);
+                (self.center, projection::equirectangular_direction(s, t))
+            }
+        };
+
+        // offset each eye from the center, toeing it in so that both converge at the same point.
+        let (eye_offset, direction) = match (eye, self.stereo) {
+            (Some(eye), Some(stereo)) => {
+                let offset = stereo.eye_offset(
+                    eye,
+                    &direction,
+                    matches!(self.projection, Projection::Equirectangular),
+                );
+                (offset, direction * stereo.convergence_distance - offset)
+            }
+            _ => (Vec3::default(), direction),
+        };
+
+        Some((
+            Ray::new(lens_center + to_world(eye_offset), to_world(direction)),
+            1.0,
+        ))
+    }
+
+    /// The ray from the center of the camera through the center of the pixel at (i, j), or `None`
+    /// if the pixel lies outside the area the camera's projection covers.
+    fn probe_ray(&self, i: u64, j: u64) -> Option<Ray> {
+        let s = (i as f32 + 0.5) / u64::from(self.image_width) as f32;
+        let t = (j as f32 + 0.5) / u64::from(self.image_height) as f32;
+        let aspect_ratio = u64::from(self.image_width) as f32 / u64::from(self.image_height) as f32;
+
+        let direction = match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                let pixel_center = self.pixel00_loc
+                    + (i as f32 * self.pixel_delta_u)
+                    + (j as f32 * self.pixel_delta_v);
+                let origin = match self.projection {
+                    Projection::Orthographic { .. } => {
+                        pixel_center - (pixel_center - self.center).dot(&self.w) * self.w
+                    }
+                    _ => self.center,
+                };
+                return Some(Ray::new(origin, pixel_center - origin));
+            }
+            Projection::Fisheye { fov, mapping } => {
+                projection::fisheye_direction(s, t, aspect_ratio, fov, mapping)?
+            }
+            Projection::Equirectangular => projection::equirectangular_direction(s, t),
+        };
+
+        Some(Ray::new(
+            self.center,
+            direction.x() * self.u + direction.y() * self.v + direction.z() * self.w,
+        ))
+    }
+
+    /// The position `(dx, dy)` pixels away from the center of the pixel at (i, j), relative to
+    /// the image: both coordinates range from `0` to `1` from the top left corner.
+    fn image_position(&self, i: u64, j: u64, (dx, dy): (f32, f32)) -> (f32, f32) {
+        let s = (i as f32 + 0.5 + dx) / u64::from(self.image_width) as f32;
+        let t = (j as f32 + 0.5 + dy) / u64::from(self.image_height) as f32;
+        (s, t)
+    }
+
+    /// The offset on the viewport of a point `(dx, dy)` pixels away from a pixel's center.
+    fn pixel_offset(&self, (dx, dy): (f32, f32)) -> Vec3 {
+        (dx * self.pixel_delta_u) + (dy * self.pixel_delta_v)
+    }
+
+    /// Computes the color seen along `ray`, tracing it either in RGB or at a set of sampled
+    /// wavelengths depending on how the camera was configured.
+    fn sample_color<World: Hittable>(
+        &self,
+        ray: &Ray,
+        world: &World,
+        lights: &LightList,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if self.spectral {
+            let mut lambda = SampledWavelengths::sample_visible(sampler.get_1d());
+            let radiance: SampledSpectrum = self.ray_color(
+                ray,
+                self.max_depth.into(),
+                world,
+                lights,
+                &mut lambda,
+                sampler,
+            );
+            lambda.to_rgb(radiance)
+        } else {
+            self.ray_color(ray, self.max_depth.into(), world, lights, &mut (), sampler)
+        }
+    }
+
+    fn ray_color<World: Hittable, S: Radiance>(
+        &self,
+        ray: &Ray,
+        depth: u32,
+        world: &World,
+        lights: &LightList,
+        lambda: &mut S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> S {
+        // if we've exceeded max depth, don't gather any more light.
+        if depth == 0 {
+            return S::default();
+        }
+
+        if let Some(record) = world.hit(ray, &(0.001..f32::INFINITY)) {
+            // a ray heading for the back of a surface is inside it, so it may scatter off whatever
+            // fills the surface before getting there.
+            let mut medium_weight = None;
+            if let Some(medium) = record.material.interior().filter(|_| !record.front_face) {
+                match medium.sample::<S>(ray, record.t, lambda, sampler) {
+                    MediumEvent::Scattered { t, weight } => {
+                        let scattered =
+                            Ray::new(ray.at(t), Vec3::sample_unit_sphere(sampler.get_2d()));
+                        return weight
+                            * self.ray_color::<World, S>(
+                                &scattered,
+                                depth - 1,
+                                world,
+                                lights,
+                                lambda,
+                                sampler,
+                            );
+                    }
+                    MediumEvent::Passed { weight } => medium_weight = Some(weight),
+                }
+            }
+
+            let direct =
+                self.direct_light::<World, S>(ray, &record, world, lights, lambda, sampler);
+            let radiance = if let Some((scattered, attenuation)) =
+                S::scatter(record.material, ray, &record, lambda, sampler)
+            {
+                let sun = self.specular_sun(ray, &record, &scattered, depth - 1, world);
+                direct
+                    + attenuation
+                        * (self.ray_color::<World, S>(
+                            &scattered,
+                            depth - 1,
+                            world,
+                            lights,
+                            lambda,
+                            sampler,
+                        ) + S::from_rgb(sun, lambda))
+            } else {
+                direct
+            };
+            return medium_weight.map_or(radiance, |weight| weight * radiance);
+        }
+        let camera_ray = depth == u32::from(self.max_depth);
+        S::from_rgb(
+            self.background.radiance(&ray.direction(), camera_ray),
+            lambda,
+        )
+    }
+
+    /// Computes the color seen along a camera ray like [`Camera::sample_color`], along with what
+    /// the ray saw for the AOVs.
+    fn sample_color_aovs<'w, World: Hittable>(
+        &self,
+        ray: &Ray,
+        world: &'w World,
+        lights: &LightList,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, AovSample<'w>) {
+        if self.spectral {
+            let mut lambda = SampledWavelengths::sample_visible(sampler.get_1d());
+            let (radiance, sample): (SampledSpectrum, _) =
+                self.ray_color_aovs(ray, world, lights, &mut lambda, sampler);
+            // the light is converted at the wavelengths left at the end of the path, like the
+            // color is.
+            (
+                lambda.to_rgb(radiance),
+                sample.map(|radiance| lambda.to_rgb(radiance)),
+            )
+        } else {
+            self.ray_color_aovs(ray, world, lights, &mut (), sampler)
+        }
+    }
+
+    /// Traces a camera ray just as [`Camera::ray_color`] does, taking the same samples so that
+    /// the color is the same, but also splits the light by the way it came, and records the
+    /// first surface the ray hits.
+    ///
+    /// Light reflected by that surface is direct if it comes from a light, or from the
+    /// background straight after the bounce, and indirect otherwise.  Light that scatters in the
+    /// medium behind the surface is all indirect.
+    fn ray_color_aovs<'w, World: Hittable, S: Radiance>(
+        &self,
+        ray: &Ray,
+        world: &'w World,
+        lights: &LightList,
+        lambda: &mut S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> (S, AovSample<'w, S>) {
+        let depth = u32::from(self.max_depth);
+        let Some(record) = world.hit(ray, &(0.001..f32::INFINITY)) else {
+            let emission = S::from_rgb(self.background.radiance(&ray.direction(), true), lambda);
+            let sample = AovSample {
+                emission,
+                ..Default::default()
+            };
+            return (emission, sample);
+        };
+        let mut sample = AovSample {
+            surface: Some(Surface::new(ray, &record)),
+            ..Default::default()
+        };
+
+        let mut medium_weight = None;
+        if let Some(medium) = record.material.interior().filter(|_| !record.front_face) {
+            match medium.sample::<S>(ray, record.t, lambda, sampler) {
+                MediumEvent::Scattered { t, weight } => {
+                    let scattered = Ray::new(ray.at(t), Vec3::sample_unit_sphere(sampler.get_2d()));
+                    let radiance = weight
+                        * self.ray_color::<World, S>(
+                            &scattered,
+                            depth - 1,
+                            world,
+                            lights,
+                            lambda,
+                            sampler,
+                        );
+                    sample.indirect = radiance;
+                    return (radiance, sample);
+                }
+                MediumEvent::Passed { weight } => medium_weight = Some(weight),
+            }
+        }
+
+        // the same sum direct_light takes, keeping each light's part.
+        let light_radiance: Vec<S> = lights
+            .iter()
+            .map(|light| {
+                self.light_radiance(ray, &record, world, light, lambda, sampler)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let direct = light_radiance
+            .iter()
+            .fold(S::default(), |acc, &radiance| acc + radiance);
+
+        let (radiance, bounced, escaped) = if let Some((scattered, attenuation)) =
+            S::scatter(record.material, ray, &record, lambda, sampler)
+        {
+            let sun = self.specular_sun(ray, &record, &scattered, depth - 1, world);
+            let bounced = attenuation
+                * (self.ray_color::<World, S>(
+                    &scattered,
+                    depth - 1,
+                    world,
+                    lights,
+                    lambda,
+                    sampler,
+                ) + S::from_rgb(sun, lambda));
+            let escaped = depth > 1 && world.hit(&scattered, &(0.001..f32::INFINITY)).is_none();
+            (direct + bounced, bounced, escaped)
+        } else {
+            (direct, S::default(), false)
+        };
+
+        let weighted = |radiance: S| medium_weight.map_or(radiance, |weight| weight * radiance);
+        if escaped {
+            sample.direct = weighted(direct + bounced);
+        } else {
+            sample.direct = weighted(direct);
+            sample.indirect = weighted(bounced);
+        }
+        sample.lights = light_radiance.into_iter().map(weighted).collect();
+        (weighted(radiance), sample)
+    }
+
+    /// The light of the sun's disk that reaches `record` along `scattered`, with `depth` bounces
+    /// left.  Light sampling only covers the directions surfaces can reflect the sun's light in
+    /// from anywhere on its disk, so bounces off surfaces that scatter light just one way, such as
+    /// mirrors and glass, see the disk themselves.
+    fn specular_sun<World: Hittable>(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        scattered: &Ray,
+        depth: u32,
+        world: &World,
+    ) -> Color {
+        let direction = scattered.direction().normalize();
+        match self.background.sun_disk(&direction) {
+            Some(sun)
+                if depth > 0
+                    && record.material.eval(ray, record, &direction).is_none()
+                    && world.hit(scattered, &(0.001..f32::INFINITY)).is_none() =>
+            {
+                sun
+            }
+            _ => Color::default(),
+        }
+    }
+
+    /// Computes the light reflected along `ray` that arrives directly from `lights`, casting a
+    /// shadow ray towards each of them.  Lights can't be hit by scattered rays, so this is the
+    /// only way their light enters the image.
+    fn direct_light<World: Hittable, S: Radiance>(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        world: &World,
+        lights: &LightList,
+        lambda: &S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> S {
+        lights
+            .iter()
+            .filter_map(|light| self.light_radiance(ray, record, world, light, lambda, sampler))
+            .fold(S::default(), |acc, radiance| acc + radiance)
+    }
+
+    /// Computes the light reflected along `ray` that arrives directly from `light`, or `None` if
+    /// none does.
+    fn light_radiance<World: Hittable, S: Radiance>(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        world: &World,
+        light: &dyn Light,
+        lambda: &S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<S> {
+        let sample = light.sample(&record.point, sampler)?;
+        let reflected = record.material.eval(ray, record, &sample.direction)?;
+        let shadow_ray = Ray::new(record.point, sample.direction);
+        match world.hit(&shadow_ray, &(0.001..sample.distance)) {
+            Some(_) => None,
+            None => Some(S::from_rgb(reflected * sample.radiance, lambda)),
+        }
+    }
+
+    /// Samples an offset from the center of the lens, as seen from the pixel at (i, j).
+    fn defocus_disk_sample(&self, i: u64, j: u64, sampler: &mut dyn Sampler) -> Vec3 {
+        let x = 2.0 * (i as f32 + 0.5) / u64::from(self.image_width) as f32 - 1.0;
+        let y = 2.0 * (j as f32 + 0.5) / u64::from(self.image_height) as f32 - 1.0;
+        let p = self.aperture.sample(x, y, sampler);
+        p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
+    }
+}
+
+/// A progress bar counting up to `pixels` pixels written.
+fn progress_bar(pixels: u64) -> indicatif::ProgressBar {
+    indicatif::ProgressBar::new(pixels)
+        .with_message("Pixels written")
+        .with_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise:8.green}] {bar:40.cyan} [ETA: {eta_precise:8.magenta}] {msg}: {percent:>3}%",
+            )
+            .unwrap(),
+        )
+}
+
+/// The image written of the eyes rendered, ready to be output.
+struct FinishedImage {
+    width: u64,
+    height: u64,
+    pixels: Vec<Pixel>,
+    /// The colors of the pixels, denoised if asked for but not yet displayed.
+    colors: Vec<Color>,
+    layers: Vec<Layer>,
+}
+
+/// What a pass over the pixels of one eye's image needs besides the pixels.
+struct EyePass<'a> {
+    eye: Option<Eye>,
+    deadline: Option<Instant>,
+    progress_bar: &'a indicatif::ProgressBar,
+    /// Where the eye's image goes in the image written to the tile output.
+    tile_origin: (u64, u64),
+}
+
+impl EyePass<'_> {
+    /// Whether the time for the pass has run out.
+    fn is_over(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CameraBuilder {
+    pub aspect_ratio: Option<f32>,
+    pub samples_per_pixel: Option<NonZeroU32>,
+    pub image_width: Option<NonZeroU64>,
+    pub max_depth: Option<NonZeroU32>,
+    pub vfov: Option<f32>,
+    pub look_from: Option<Point3>,
+    pub look_at: Option<Point3>,
+    pub up: Option<Vec3>,
+    pub defocus_angle: Option<f32>,
+    pub focus_dist: Option<f32>,
+    pub focus_point: Option<Point3>,
+    pub spectral: Option<bool>,
+    pub background: Option<Background>,
+    pub projection: Option<Projection>,
+    pub stereo: Option<Stereo>,
+    pub lens: Option<LensSystem>,
+    pub sampler: Option<SamplerKind>,
+    pub filter: Option<Filter>,
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    pub sample_heatmap: Option<PathBuf>,
+    pub progressive: Option<Progressive>,
+    pub checkpoint: Option<Checkpoint>,
+    pub time_limit: Option<Duration>,
+    pub crop: Option<Crop>,
+    pub tiles: Option<Tiles>,
+    pub tile_output: Option<Arc<dyn TileOutput>>,
+    pub aovs: Option<Aovs>,
+    pub denoise: Option<Denoise>,
+    pub output: Option<OutputTransform>,
+    pub seed: Option<u64>,
+    pub physical: Option<PhysicalCamera>,
+    pub aperture: Option<Aperture>,
+}
+
+impl TryFrom<CameraBuilder> for Camera {
+    type Error = std::io::Error;
+
+    /// Fails if the settings don't describe an image that can be rendered.
+    fn try_from(val: CameraBuilder) -> std::io::Result<Self> {
+        // a checkpoint can be resumed with more samples, or saved somewhere else, but only with
+        // the same view of the scene.
+        let settings = CameraBuilder {
+            samples_per_pixel: None,
+            adaptive_sampling: None,
+            sample_heatmap: None,
+            progressive: None,
+            checkpoint: None,
+            time_limit: None,
+            crop: val.crop.map(|crop| crop.with_full_frame(false)),
+            tiles: None,
+            tile_output: None,
+            aovs: None,
+            denoise: None,
+            output: None,
+            ..val.clone()
+        };
+        let settings_hash = checkpoint::hash(&[format!("{settings:?}").as_bytes()]);
+
+        let aspect_ratio = val.aspect_ratio.unwrap_or(1.0);
+        // adaptive sampling takes up to its maximum number of samples instead.
+        let samples_per_pixel = match val.adaptive_sampling {
+            Some(adaptive) => NonZeroU32::new(adaptive.max_samples_per_pixel),
+            None => val.samples_per_pixel,
+        }
+        .unwrap_or_else(|| unsafe { NonZeroU32::new_unchecked(10) });
+
+        let max_depth = val
+            .max_depth
+            .unwrap_or_else(|| unsafe { NonZeroU32::new_unchecked(10) });
+
+        let (image_width, image_height) = val.image_size();
+
+        let crop = match val.crop {
+            Some(crop) => crop.region(image_width.into(), image_height.into())?,
+            None => Tile {
+                x: 0,
+                y: 0,
+                width: image_width.into(),
+                height: image_height.into(),
+            },
+        };
+
+        // writing finished tiles needs the image to be rendered in tiles.
+        let tiles = match (val.tiles, &val.tile_output) {
+            (Some(tiles), _) => tiles.layout(crop.width, crop.height),
+            (None, Some(_)) => Tiles::default().layout(crop.width, crop.height),
+            (None, None) => Vec::new(),
+        };
+
+        let mut gathered_aovs = val
+            .aovs
+            .as_ref()
+            .map_or_else(Vec::new, |aovs| aovs.buffers.clone());
+        if val.denoise.is_some() {
+            for guide in Denoise::GUIDES {
+                if !gathered_aovs.contains(&guide) {
+                    gathered_aovs.push(guide);
+                }
+            }
+        }
+
+        let (look_from, look_at) = val.placement();
+        let up = val.up.unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0));
+
+        let focus_dist = match val.focus_point {
+            Some(point) => val.focus_dist_to(point)?,
+            None => val.focus_dist.unwrap_or(10.0),
+        };
+
+        // a physical description of the camera takes precedence over the raw angles.
+        let (vfov, defocus_angle, exposure) = match val.physical {
+            Some(physical) => (
+                physical.vertical_fov(aspect_ratio),
+                2.0 * (physical.aperture_radius() / focus_dist)
+                    .atan()
+                    .to_degrees(),
+                physical.exposure(),
+            ),
+            None => (
+                val.vfov.unwrap_or(90.0),
+                val.defocus_angle.unwrap_or(0.0),
+                1.0,
+            ),
+        };
+        let spectral = val.spectral.unwrap_or(false);
+        let background = val.background.unwrap_or_default();
+        let projection = val.projection.unwrap_or_default();
+        let lens = val.lens.map(|mut lens| {
+            lens.prepare(
+                u64::from(image_width) as f32 / u64::from(image_height) as f32,
+                focus_dist,
+            );
+            lens
+        });
+
+        let center = look_from;
+
+        // determine viewport dimensions
+        let theta = vfov * (PI / 180.0);
+        let h = (theta * 0.5).tan();
+        let viewport_height = match projection {
+            Projection::Orthographic { view_height } => view_height,
+            _ => 2.0 * h * focus_dist,
+        };
+        let viewport_width = viewport_height * aspect_ratio;
+
+        // calculate the u,v,w unit basis vectors for the camera coordinate frame
+        let w = (look_from - look_at).normalize();
+        let u = up.cross(&w).normalize();
+        let v = w.cross(&u);
+
+        let viewport_u = viewport_width * u;
+        let viewport_v = viewport_height * -v;
+
+        let pixel_delta_u = viewport_u / u64::from(image_width) as f32;
+        let pixel_delta_v = viewport_v / u64::from(image_height) as f32;
+
+        let viewport_upper_left = center - (focus_dist * w) - viewport_u * 0.5 - viewport_v * 0.5;
+        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+        let defocus_radius = focus_dist * ((0.5 * defocus_angle).to_radians()).tan();
+        let defocus_disk_u = u * defocus_radius;
+        let defocus_disk_v = v * defocus_radius;
+
+        Ok(Camera {
+            samples_per_pixel,
+            max_depth,
+            image_width,
+            image_height,
+            crop,
+            crop_full_frame: val.crop.is_some_and(|crop| crop.full_frame),
+            center,
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            defocus_angle,
+            focus_dist,
+            display: val.output.unwrap_or_default().display(exposure),
+            defocus_disk_u,
+            defocus_disk_v,
+            aperture: val.aperture.unwrap_or_default(),
+            u,
+            v,
+            w,
+            projection,
+            stereo: val.stereo,
+            lens,
+            sampler: val.sampler.unwrap_or_default(),
+            filter: FilterSampler::new(val.filter.unwrap_or_default()),
+            adaptive_sampling: val.adaptive_sampling,
+            sample_heatmap: val.sample_heatmap,
+            progressive: val.progressive,
+            checkpoint: val.checkpoint,
+            time_limit: val.time_limit,
+            tiles,
+            tile_output: val.tile_output,
+            aovs: val.aovs,
+            gathered_aovs,
+            denoise: val.denoise,
+            settings_hash,
+            seed: val.seed.unwrap_or_default(),
+            spectral,
+            background,
+        })
+    }
+}
+
+impl CameraBuilder {
+    /// Where the camera is, and the point it looks at, as configured so far.
+    fn placement(&self) -> (Point3, Point3) {
+        (
+            self.look_from
+                .unwrap_or_else(|| Point3::new(0.0, 0.0, -1.0)),
+            self.look_at.unwrap_or_else(|| Point3::new(0.0, 0.0, 0.0)),
+        )
+    }
+
+    /// The distance to the plane of focus that passes through `point`, failing if the point
+    /// isn't in front of the camera as it's placed so far.
+    fn focus_dist_to(&self, point: Point3) -> std::io::Result<f32> {
+        let (look_from, look_at) = self.placement();
+        let distance = (look_at - look_from).normalize().dot(&(point - look_from));
+        if distance > 0.0 {
+            Ok(distance)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "the focus point {},{},{} isn't in front of the camera",
+                    point.x(),
+                    point.y(),
+                    point.z()
+                ),
+            ))
+        }
+    }
+
+    /// The width and height of the image, as configured so far.
+    pub fn image_size(&self) -> (NonZeroU64, NonZeroU64) {
+        let image_width = self
+            .image_width
+            // Safety: new_unchecked requires the argument to be non-zero, which 100 satisfies.
+            .unwrap_or_else(|| unsafe { NonZeroU64::new_unchecked(100) });
+
+        let aspect_ratio = self.aspect_ratio.unwrap_or(1.0);
+        let image_height =
+            NonZeroU64::new(((u64::from(image_width) as f32 / aspect_ratio) as u64).max(1))
+                .expect("Image width is zero");
+        (image_width, image_height)
+    }
+
+    /// Sets the aspect ratio for the camera.
+    pub fn with_aspect_ratio(mut self, aspect_ratio: f32) -> Self {
+        self.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    /// Sets the image width for the camera.  If the passed image width is `0`, then the camera
+    /// uses the default of 100 pixels.
+    pub fn with_image_width(mut self, image_width: u64) -> Self {
+        self.image_width = NonZeroU64::new(image_width);
+        self
+    }
+
+    /// Sets the sample depth for the camera.  If the passed sample depth is `0`, then the camera
+    /// uses its default of `10` samples per pixel.
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.samples_per_pixel = NonZeroU32::new(samples_per_pixel);
+        self
+    }
+
+    /// Sets the recursion depth for ray reflections.  Defaults to 10.
+    pub fn with_recursion_depth(mut self, depth: u32) -> Self {
+        self.max_depth = NonZeroU32::new(depth);
+        self
+    }
+
+    pub fn with_vertical_field_of_view(mut self, fov: f32) -> Self {
+        self.vfov = Some(fov);
+        self
+    }
+
+    pub fn look_from(mut self, from: Point3) -> Self {
+        self.look_from = Some(from);
+        self
+    }
+
+    pub fn look_at(mut self, at: Point3) -> Self {
+        self.look_at = Some(at);
+        self
+    }
+
+    pub fn with_up(mut self, up: Vec3) -> Self {
+        self.up = Some(up);
+        self
+    }
+
+    pub fn with_defocus_angle(mut self, angle: f32) -> Self {
+        self.defocus_angle = Some(angle);
+        self
+    }
+
+    /// Sets the distance to the plane of focus, in place of any point the camera was focused on.
+    pub fn with_focus_dist(mut self, dist: f32) -> Self {
+        self.focus_dist = Some(dist);
+        self.focus_point = None;
+        self
+    }
+
+    /// Focuses the camera on `point`, in place of any focus distance set before.  Fails if the
+    /// point isn't in front of the camera as it's placed so far, and so does building the camera
+    /// if it's moved so that the point isn't.
+    pub fn with_focus_point(mut self, point: Point3) -> std::io::Result<Self> {
+        self.focus_dist_to(point)?;
+        self.focus_point = Some(point);
+        Ok(self)
+    }
+
+    /// Focuses the camera on whatever the ray through the center of the pixel at (i, j) hits
+    /// first in `world`.  Leaves the focus unchanged if the ray escapes the scene, and fails if
+    /// the pixel lies outside the image or sees something that isn't in front of the camera, as
+    /// a wide fisheye can.
+    ///
+    /// The probe ray is cast with the camera as it's configured so far, so this should be called
+    /// after setting the camera's position and field of view.
+    pub fn with_focus_on_pixel<World>(self, world: &World, i: u64, j: u64) -> std::io::Result<Self>
+    where
+        World: Hittable,
+    {
+        let camera = self.clone().build()?;
+        let (width, height) = (camera.image_width.get(), camera.image_height.get());
+        if i >= width || j >= height {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("pixel {i},{j} lies outside the {width}x{height} image"),
+            ));
+        }
+
+        let hit = camera
+            .probe_ray(i, j)
+            .and_then(|ray| world.hit(&ray, &(0.001..f32::INFINITY)));
+        match hit {
+            Some(hit) => self.with_focus_point(hit.point),
+            None => Ok(self),
+        }
+    }
+
+    /// Traces paths at sampled wavelengths instead of in RGB, so that wavelength-dependent
+    /// effects such as dispersion show up.  Defaults to false.
+    pub fn with_spectral_rendering(mut self, spectral: bool) -> Self {
+        self.spectral = Some(spectral);
+        self
+    }
+
+    /// Sets what rays that escape the scene see.  Defaults to a white-to-blue gradient.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    /// Sets how the camera projects the scene onto the image.  Defaults to a perspective
+    /// projection.
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    /// Renders a stereo pair instead of a single image.
+    pub fn with_stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    /// Traces rays through a system of lens elements instead of projecting them, with the film at
+    /// the camera's position.  This replaces the projection, field of view and defocus blur, and
+    /// renders a single view even for stereo cameras.
+    pub fn with_lens_system(mut self, lens: LensSystem) -> Self {
+        self.lens = Some(lens);
+        self
+    }
+
+    /// Describes the camera by its lens, sensor and exposure settings.  This replaces the vertical
+    /// field of view and defocus angle, and scales the brightness of the image by the exposure.
+    pub fn with_physical_camera(mut self, physical: PhysicalCamera) -> Self {
+        self.physical = Some(physical);
+        self
+    }
+
+    /// Sets the shape of the lens's aperture, which shapes out-of-focus highlights.  Defaults to
+    /// a circle.
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = Some(aperture);
+        self
+    }
+
+    /// Sets where the values for the renderer's random choices come from.  Defaults to
+    /// independent random numbers.
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Sets the filter that weighs samples by their position around each pixel.  Defaults to a
+    /// box filter covering just the pixel.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Samples each pixel only until its estimated error is small enough, between the minimum
+    /// and maximum numbers of samples `adaptive` allows.  Replaces the samples per pixel.
+    pub fn with_adaptive_sampling(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.adaptive_sampling = Some(adaptive);
+        self
+    }
+
+    /// Also writes a PPM image of how many samples each pixel got to `path`, for inspecting
+    /// adaptive sampling.
+    pub fn with_sample_heatmap(mut self, path: PathBuf) -> Self {
+        self.sample_heatmap = Some(path);
+        self
+    }
+
+    /// Saves the samples taken so far to a checkpoint every so often, or resumes from one, as
+    /// `checkpoint` describes.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Keeps taking passes of samples over the image until `time_limit` has passed, rather than
+    /// stopping at the samples per pixel, then finishes the image with however many samples each
+    /// pixel got.  Adaptive sampling can still finish pixels sooner.
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    /// Renders only the region of the image `crop` describes, seen just as it is in the full
+    /// image.  Building the camera fails if the region lies outside the image.
+    pub fn with_crop(mut self, crop: Crop) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    /// Renders the image in tiles, each taken on by a single thread, in the order `tiles` gives.
+    pub fn with_tiles(mut self, tiles: Tiles) -> Self {
+        self.tiles = Some(tiles);
+        self
+    }
+
+    /// Writes each tile of the image to `tile_output` as soon as it's finished.  Renders in tiles
+    /// of the default size unless told otherwise.
+    pub fn with_tile_output(mut self, tile_output: Arc<dyn TileOutput>) -> Self {
+        self.tile_output = Some(tile_output);
+        self
+    }
+
+    /// Also renders the buffers `aovs` asks for alongside the image, and writes them where it
+    /// says.  Checkpoints save them along with the image, so renders can only be resumed with
+    /// the same buffers.  Distributed renders don't write them.
+    pub fn with_aovs(mut self, aovs: Aovs) -> Self {
+        self.aovs = Some(aovs);
+        self
+    }
+
+    /// Smooths away the noise left in the finished image as `denoise` describes, guided by the
+    /// albedo, normals and depth gathered alongside it.  Snapshots, tiles and checkpoints are
+    /// left as they are, and distributed renders aren't denoised, since workers don't send the
+    /// guides back.
+    pub fn with_denoise(mut self, denoise: Denoise) -> Self {
+        self.denoise = Some(denoise);
+        self
+    }
+
+    /// Exposes, white balances and tone maps the image as `output` describes, on top of any
+    /// physical camera's exposure.  Defaults to clipping the light at white.  AOVs of light, and
+    /// the image in an EXR of them, are only exposed, keeping the light as it is otherwise.
+    pub fn with_output_transform(mut self, output: OutputTransform) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Seeds the samplers, so that renders with different seeds get different noise.  Defaults to
+    /// 0.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Renders in passes over the whole image, writing snapshots of the image so far as
+    /// `progressive` describes.
+    pub fn with_progressive(mut self, progressive: Progressive) -> Self {
+        self.progressive = Some(progressive);
+        self
+    }
+
+    /// Builds the camera, failing if the settings don't describe an image that can be
+    /// rendered, such as one cropped to a window outside it.
+    pub fn build(self) -> std::io::Result<Camera> {
+        self.try_into()
+    }
+}
+
+/// Inserts the name of `eye` into `path`, e.g. `out.ppm` becomes `out-left.ppm`.
+fn eye_path(path: &Path, eye: Eye) -> PathBuf {
+    let suffix = match eye {
+        Eye::Left => "left",
+        Eye::Right => "right",
+    };
+    suffixed_path(path, suffix)
+}
@@ -0,0 +1,108 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// A camera described the way photographers describe them, rather than by field of view and
+/// defocus angle.
+#[derive(Debug, Copy, Clone)]
+pub struct PhysicalCamera {
+    /// Focal length of the lens, in millimeters.
+    pub focal_length: f32,
+    /// Width of the sensor, in millimeters.
+    pub sensor_width: f32,
+    /// Height of the sensor, in millimeters.
+    pub sensor_height: f32,
+    /// Aperture, as an f-number (the focal length divided by the diameter of the aperture).
+    pub f_number: f32,
+    /// Shutter time, in seconds.
+    pub shutter_time: f32,
+    /// Sensitivity of the sensor.
+    pub iso: f32,
+    /// How many scene units make up a meter.
+    pub units_per_meter: f32,
+}
+
+impl PhysicalCamera {
+    /// Creates a full frame (36mm by 24mm) camera with a lens of the given focal length (in
+    /// millimeters), exposed at f/16 for 1/100s at ISO 100.  Fails unless the focal length is
+    /// positive, as each of the settings below has to be.
+    pub fn new(focal_length: f32) -> Result<Self> {
+        Ok(Self {
+            focal_length: positive("focal length", focal_length)?,
+            sensor_width: 36.0,
+            sensor_height: 24.0,
+            f_number: 16.0,
+            shutter_time: 0.01,
+            iso: 100.0,
+            units_per_meter: 1.0,
+        })
+    }
+
+    /// Sets the size of the sensor, in millimeters.
+    pub fn with_sensor(mut self, width: f32, height: f32) -> Result<Self> {
+        self.sensor_width = positive("sensor width", width)?;
+        self.sensor_height = positive("sensor height", height)?;
+        Ok(self)
+    }
+
+    pub fn with_f_number(mut self, f_number: f32) -> Result<Self> {
+        self.f_number = positive("f-number", f_number)?;
+        Ok(self)
+    }
+
+    /// Sets the shutter time, in seconds.
+    pub fn with_shutter_time(mut self, shutter_time: f32) -> Result<Self> {
+        self.shutter_time = positive("shutter time", shutter_time)?;
+        Ok(self)
+    }
+
+    pub fn with_iso(mut self, iso: f32) -> Result<Self> {
+        self.iso = positive("ISO", iso)?;
+        Ok(self)
+    }
+
+    /// Sets how many scene units make up a meter.  Defaults to 1.
+    pub fn with_units_per_meter(mut self, units_per_meter: f32) -> Result<Self> {
+        self.units_per_meter = positive("number of units per meter", units_per_meter)?;
+        Ok(self)
+    }
+
+    /// The vertical field of view, in degrees, of an image with the given aspect ratio.  The
+    /// image is cropped from the middle of the sensor, as large as it can be while still fitting.
+    pub fn vertical_fov(&self, aspect_ratio: f32) -> f32 {
+        let image_height = if aspect_ratio >= self.sensor_width / self.sensor_height {
+            self.sensor_width / aspect_ratio
+        } else {
+            self.sensor_height
+        };
+
+        2.0 * (image_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    /// Radius of the aperture, in scene units.
+    pub fn aperture_radius(&self) -> f32 {
+        let radius_mm = 0.5 * self.focal_length / self.f_number;
+        radius_mm * 1e-3 * self.units_per_meter
+    }
+
+    /// How much to scale the scene's radiance by to account for the exposure.
+    ///
+    /// Exposure grows linearly with the shutter time and sensitivity, and with the area of the
+    /// aperture.  It's calibrated to the "sunny 16" rule: shooting at f/16 with a shutter time of
+    /// 1/ISO doesn't change the image's brightness.
+    pub fn exposure(&self) -> f32 {
+        256.0 * self.shutter_time * self.iso / (self.f_number * self.f_number)
+    }
+}
+
+/// `value`, or an error naming the `setting` if it isn't positive.
+fn positive(setting: &str, value: f32) -> Result<f32> {
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("the {setting} has to be positive, not {value}"),
+        ))
+    }
+}
@@ -0,0 +1,35 @@
+use std::{path::PathBuf, time::Duration};
+
+/// Settings for progressive rendering, which takes a few samples for every pixel at a time, and
+/// every so often replaces the image at `snapshot_path` with the image so far.  Long renders can
+/// then be previewed while they run, and stopped once they look good enough.
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    pub snapshot_path: PathBuf,
+    /// How many samples to take for every pixel in each pass over the image.
+    pub samples_per_pass: u32,
+    /// How long to wait at least between snapshots.  Snapshots are only taken between passes.
+    pub snapshot_interval: Duration,
+}
+
+impl Progressive {
+    /// Creates progressive rendering settings that take a snapshot to `snapshot_path` every ten
+    /// seconds, with passes of four samples per pixel.
+    pub fn new(snapshot_path: PathBuf) -> Self {
+        Self {
+            snapshot_path,
+            samples_per_pass: 4,
+            snapshot_interval: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_samples_per_pass(mut self, samples_per_pass: u32) -> Self {
+        self.samples_per_pass = samples_per_pass.max(1);
+        self
+    }
+
+    pub fn with_snapshot_interval(mut self, snapshot_interval: Duration) -> Self {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+}
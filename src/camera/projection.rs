@@ -0,0 +1,79 @@
+use std::f32::consts::PI;
+
+use crate::vec3::Vec3;
+
+/// How a camera maps directions in the scene onto the image.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum Projection {
+    /// A pinhole or thin lens camera, with a field of view set by the vertical field of view.
+    #[default]
+    Perspective,
+    /// Parallel rays, covering `view_height` units of the scene vertically.  Useful for technical
+    /// drawings, where distances shouldn't shrink with depth.
+    Orthographic { view_height: f32 },
+    /// A fisheye lens covering `fov` degrees across the width of the image.
+    Fisheye { fov: f32, mapping: FisheyeMapping },
+    /// A full 360° by 180° panorama, mapping longitude and latitude linearly onto the image.
+    /// Usually rendered at an aspect ratio of 2:1.
+    Equirectangular,
+}
+
+/// How a fisheye lens maps the angle away from its axis to a distance from the image center.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum FisheyeMapping {
+    /// Distance from the center is proportional to the angle.
+    #[default]
+    Equidistant,
+    /// Every pixel covers the same solid angle.
+    Equisolid,
+}
+
+/// Returns the direction a fisheye lens sees through the image position `(s, t)`, where both
+/// range from `0` to `1` from the top left corner, or `None` if the lens doesn't cover that
+/// position.  Directions are in camera space, with `+x` to the right, `+y` up and the camera
+/// looking down `-z`.
+pub(super) fn fisheye_direction(
+    s: f32,
+    t: f32,
+    aspect_ratio: f32,
+    fov: f32,
+    mapping: FisheyeMapping,
+) -> Option<Vec3> {
+    let x = 2.0 * s - 1.0;
+    let y = (1.0 - 2.0 * t) / aspect_ratio;
+    let r = (x * x + y * y).sqrt();
+
+    let half_fov = 0.5 * fov.to_radians();
+    let theta = match mapping {
+        FisheyeMapping::Equidistant => r * half_fov,
+        FisheyeMapping::Equisolid => {
+            let sin_half_theta = r * (0.5 * half_fov).sin();
+            if sin_half_theta > 1.0 {
+                return None;
+            }
+            2.0 * sin_half_theta.asin()
+        }
+    };
+    if theta > PI {
+        return None;
+    }
+
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let (cos_phi, sin_phi) = if r > 0.0 { (x / r, y / r) } else { (1.0, 0.0) };
+    Some(Vec3::new(
+        sin_theta * cos_phi,
+        sin_theta * sin_phi,
+        -cos_theta,
+    ))
+}
+
+/// Returns the direction an equirectangular panorama sees through the image position `(s, t)`, in
+/// the same coordinates as [`fisheye_direction`].
+pub(super) fn equirectangular_direction(s: f32, t: f32) -> Vec3 {
+    let longitude = (2.0 * s - 1.0) * PI;
+    let latitude = (0.5 - t) * PI;
+
+    let (sin_lat, cos_lat) = latitude.sin_cos();
+    let (sin_lon, cos_lon) = longitude.sin_cos();
+    Vec3::new(cos_lat * sin_lon, sin_lat, -cos_lat * cos_lon)
+}
@@ -0,0 +1,68 @@
+use crate::vec3::Vec3;
+
+/// One eye of a stereo pair.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// How the two eyes of a stereo pair are arranged in a single image.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum StereoLayout {
+    /// The left eye in the left half of the image, and the right eye in the right half.
+    #[default]
+    SideBySide,
+    /// The left eye in the top half of the image, and the right eye in the bottom half.
+    TopBottom,
+}
+
+/// Settings for rendering stereo pairs.
+#[derive(Debug, Copy, Clone)]
+pub struct Stereo {
+    /// Distance between the two eyes.
+    pub interpupillary_distance: f32,
+    /// Distance from the camera at which the eyes' views converge, i.e. where objects appear at
+    /// the depth of the screen.
+    pub convergence_distance: f32,
+    pub layout: StereoLayout,
+}
+
+impl Stereo {
+    /// Creates stereo settings with eyes `interpupillary_distance` apart, converging
+    /// `convergence_distance` away from the camera.
+    pub fn new(interpupillary_distance: f32, convergence_distance: f32) -> Self {
+        Self {
+            interpupillary_distance,
+            convergence_distance,
+            layout: StereoLayout::default(),
+        }
+    }
+
+    pub fn with_layout(mut self, layout: StereoLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Offset of `eye` from the center of the camera, in camera space, when looking along
+    /// `direction`.
+    ///
+    /// For projections that see all around the camera, this gives omni-directional stereo: the
+    /// eyes sit on a circle, always perpendicular to the horizontal part of the viewing
+    /// direction.  The offset shrinks to nothing looking straight up or down, where there's no
+    /// consistent way to place the eyes.
+    pub(super) fn eye_offset(&self, eye: Eye, direction: &Vec3, omnidirectional: bool) -> Vec3 {
+        let half_distance = match eye {
+            Eye::Left => -0.5 * self.interpupillary_distance,
+            Eye::Right => 0.5 * self.interpupillary_distance,
+        };
+
+        let right = if omnidirectional {
+            Vec3::new(-direction.z(), 0.0, direction.x()) / direction.len()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+
+        half_distance * right
+    }
+}
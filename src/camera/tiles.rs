@@ -0,0 +1,239 @@
+use std::{
+    fs::File,
+    io::{Result, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// The order tiles are rendered in.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum TileOrder {
+    /// Row by row, from the top left.
+    Scanline,
+    /// Spiralling out from the center of the image, which usually holds what's most interesting.
+    Spiral,
+    /// Along a Hilbert curve, which never jumps between tiles that aren't next to each other, so
+    /// tiles rendered around the same time see similar parts of the scene.
+    #[default]
+    Hilbert,
+    /// Along a Morton (Z-order) curve, which visits tiles in ever larger square blocks.
+    Morton,
+}
+
+/// Settings for rendering the image in square tiles, each rendered by a single thread, so that
+/// the rays a thread traces stay close together and keep hitting the same parts of the scene.
+#[derive(Debug, Copy, Clone)]
+pub struct Tiles {
+    /// The width and height of each tile, in pixels.
+    pub size: u32,
+    pub order: TileOrder,
+}
+
+impl Default for Tiles {
+    /// 32 by 32 pixel tiles, in Hilbert curve order.
+    fn default() -> Self {
+        Self::new(32)
+    }
+}
+
+impl Tiles {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size: size.max(1),
+            order: TileOrder::default(),
+        }
+    }
+
+    pub fn with_order(mut self, order: TileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Divides a `width` by `height` image into tiles, in the order they're to be rendered.
+    /// Tiles along the right and bottom edges are cut short to fit.
+    pub(super) fn layout(&self, width: u64, height: u64) -> Vec<Tile> {
+        let size = u64::from(self.size);
+        let columns = width.div_ceil(size);
+        let rows = height.div_ceil(size);
+
+        let mut coordinates: Vec<(u64, u64)> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect();
+        match self.order {
+            TileOrder::Scanline => {}
+            TileOrder::Spiral => coordinates = spiral(columns, rows),
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                coordinates.sort_by_key(|&(x, y)| hilbert_index(side, x, y));
+            }
+            TileOrder::Morton => coordinates.sort_by_key(|&(x, y)| morton_index(x, y)),
+        }
+
+        coordinates
+            .into_iter()
+            .map(|(column, row)| {
+                let (x, y) = (column * size, row * size);
+                Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A rectangle of pixels, from `(x, y)` at its top left.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+impl Tile {
+    /// The indices of the tile's pixels in the row-major pixels of an image `image_width` wide,
+    /// row by row.
+    pub(super) fn pixel_indices(&self, image_width: u64) -> impl Iterator<Item = usize> + '_ {
+        (self.y..self.y + self.height).flat_map(move |j| {
+            (self.x..self.x + self.width).map(move |i| (j * image_width + i) as usize)
+        })
+    }
+}
+
+/// Somewhere to write tiles of the final image to as soon as they're finished, rather than
+/// waiting for the whole image.
+pub trait TileOutput: std::fmt::Debug + Send + Sync {
+    /// Prepares for an image `width` by `height` pixels, before any tiles are written.
+    fn begin(&self, width: u64, height: u64) -> Result<()>;
+
+    /// Writes the 8 bit sRGB colors of the pixels of `tile`, row by row.  Each tile is written
+    /// once, in no particular order, and possibly from several threads at once.
+    fn write_tile(&self, tile: &Tile, pixels: &[[u8; 3]]) -> Result<()>;
+}
+
+/// Writes tiles into a binary PPM file as they're finished, so that the image fills in while it
+/// renders.  Pixels not yet rendered are black.
+#[derive(Debug)]
+pub struct PpmTileOutput {
+    path: PathBuf,
+    file: Mutex<Option<PpmFile>>,
+}
+
+#[derive(Debug)]
+struct PpmFile {
+    file: File,
+    width: u64,
+    header_length: u64,
+}
+
+impl PpmTileOutput {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: Mutex::new(None),
+        }
+    }
+}
+
+impl TileOutput for PpmTileOutput {
+    fn begin(&self, width: u64, height: u64) -> Result<()> {
+        let header = format!("P6\n{} {}\n255\n", width, height);
+        let mut file = File::create(&self.path)?;
+        file.write_all(header.as_bytes())?;
+        file.set_len(header.len() as u64 + 3 * width * height)?;
+
+        *self.file.lock().unwrap() = Some(PpmFile {
+            file,
+            width,
+            header_length: header.len() as u64,
+        });
+        Ok(())
+    }
+
+    fn write_tile(&self, tile: &Tile, pixels: &[[u8; 3]]) -> Result<()> {
+        let mut guard = self.file.lock().unwrap();
+        let Some(ppm) = guard.as_mut() else {
+            return Ok(());
+        };
+
+        for (row, colors) in pixels.chunks(tile.width as usize).enumerate() {
+            let offset = ppm.header_length + 3 * ((tile.y + row as u64) * ppm.width + tile.x);
+            ppm.file.seek(SeekFrom::Start(offset))?;
+            ppm.file.write_all(colors.as_flattened())?;
+        }
+        Ok(())
+    }
+}
+
+/// The tiles of a `columns` by `rows` grid, spiralling out clockwise from the center.
+fn spiral(columns: u64, rows: u64) -> Vec<(u64, u64)> {
+    let total = (columns * rows) as usize;
+    let mut coordinates = Vec::with_capacity(total);
+    let (mut x, mut y) = ((columns as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+    // walk straight runs of 1, 1, 2, 2, 3, 3, ... tiles, turning after each, and keep the tiles
+    // that land inside the grid.
+    let mut run = 1;
+    let mut direction = 0;
+    let visit = |x: i64, y: i64, coordinates: &mut Vec<(u64, u64)>| {
+        if (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y) {
+            coordinates.push((x as u64, y as u64));
+        }
+    };
+    visit(x, y, &mut coordinates);
+    while coordinates.len() < total {
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction % 4];
+            for _ in 0..run {
+                x += dx;
+                y += dy;
+                visit(x, y, &mut coordinates);
+            }
+            direction += 1;
+        }
+        run += 1;
+    }
+
+    coordinates
+}
+
+/// How far along a Hilbert curve filling a `side` by `side` grid the cell `(x, y)` is, where
+/// `side` is a power of two.
+fn hilbert_index(side: u64, mut x: u64, mut y: u64) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        index += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the curve inside it joins up with the next.
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Interleaves the bits of `x` and `y`, giving the position of `(x, y)` along a Morton curve.
+fn morton_index(x: u64, y: u64) -> u64 {
+    let spread = |mut v: u64| {
+        v &= 0xffff_ffff;
+        v = (v | (v << 16)) & 0x0000_ffff_0000_ffff;
+        v = (v | (v << 8)) & 0x00ff_00ff_00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+        v
+    };
+    spread(x) | (spread(y) << 1)
+}
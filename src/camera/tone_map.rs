@@ -0,0 +1,231 @@
+use crate::{
+    spectrum::{cie_xyz, xyz_to_linear_srgb},
+    vec3::Color,
+};
+
+/// How light too bright for the display is brought into its range.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum ToneMap {
+    /// Clips each channel at white, so that highlights burn out.
+    #[default]
+    Clamp,
+    /// Reinhard's `L / (1 + L)`, applied to the luminance so that hues are kept.  Nothing quite
+    /// reaches white.
+    Reinhard,
+    /// Reinhard's curve stretched so that the luminance `white` maps to white.
+    ExtendedReinhard { white: f32 },
+    /// John Hable's filmic curve from Uncharted 2, applied to each channel.
+    Hable,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms.
+    Aces,
+    /// Troy Sobotka's AgX, using the polynomial fit of its sigmoid, which desaturates bright
+    /// colors towards white as film does.
+    Agx,
+}
+
+impl ToneMap {
+    /// Maps `color`, in linear sRGB as exposed, to the display's linear range of 0 to 1.
+    fn apply(&self, color: Color) -> Color {
+        let color = match *self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                let white_squared = (white * white).max(f32::EPSILON);
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            ToneMap::Hable => {
+                const EXPOSURE_BIAS: f32 = 2.0;
+                const WHITE: f32 = 11.2;
+                let white_scale = hable(WHITE).recip();
+                per_channel(color, |c| hable(EXPOSURE_BIAS * c.max(0.0)) * white_scale)
+            }
+            ToneMap::Aces => {
+                let color = multiply(&ACES_INPUT, per_channel(color, |c| c.max(0.0)));
+                let color = per_channel(color, |c| {
+                    let a = c * (c + 0.024_578_6) - 0.000_090_537;
+                    let b = c * (0.983_729 * c + 0.432_951) + 0.238_081;
+                    a / b
+                });
+                multiply(&ACES_OUTPUT, color)
+            }
+            ToneMap::Agx => {
+                const MIN_EV: f32 = -12.473_93;
+                const MAX_EV: f32 = 4.026_069;
+                let color = multiply(&AGX_INSET, per_channel(color, |c| c.max(1e-10)));
+                let color = per_channel(color, |c| {
+                    let x = (c.log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+                    let (x2, x4) = (x * x, x * x * x * x);
+                    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x
+                        + 0.4298 * x2
+                        + 0.1191 * x
+                        - 0.002_32
+                });
+                // the sigmoid gives values encoded for a display with a gamma of 2.2.
+                per_channel(multiply(&AGX_OUTSET, color), |c| c.max(0.0).powf(2.2))
+            }
+        };
+        per_channel(color, |c| c.clamp(0.0, 1.0))
+    }
+}
+
+/// Settings for turning the light reaching the camera into the colors of the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutputTransform {
+    /// How many stops to brighten the image by, on top of any physical camera's exposure.
+    pub exposure: f32,
+    /// The color temperature, in kelvin, of light that's shown as white, or `None` to show light
+    /// of equal energy at every wavelength as white.
+    pub white_balance: Option<f32>,
+    pub tone_map: ToneMap,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            white_balance: None,
+            tone_map: ToneMap::default(),
+        }
+    }
+}
+
+impl OutputTransform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many stops to brighten the image by.  Negative values darken it.
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Sets the color temperature, in kelvin, of light that's shown as white.
+    pub fn with_white_balance(mut self, temperature: f32) -> Self {
+        self.white_balance = Some(temperature);
+        self
+    }
+
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// How much to scale each channel by for light of the white balance's temperature to come
+    /// out white, keeping its luminance.
+    fn white_balance_gains(&self) -> Color {
+        let Some(temperature) = self.white_balance else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+        let white = blackbody_rgb(temperature);
+        let white = white / luminance(white);
+        per_channel(white, |c| c.max(f32::EPSILON).recip())
+    }
+
+    /// Prepares the transform for turning light exposed by `exposure` into colors.
+    pub(super) fn display(&self, exposure: f32) -> Display {
+        Display {
+            exposure: exposure * self.exposure.exp2(),
+            gains: self.white_balance_gains(),
+            tone_map: self.tone_map,
+        }
+    }
+}
+
+/// An [`OutputTransform`] ready to be applied to the pixels of an image.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Display {
+    /// How much to scale the light reaching the camera by, for images that keep it linear.
+    pub(super) exposure: f32,
+    gains: Color,
+    tone_map: ToneMap,
+}
+
+impl Display {
+    /// The color, in the display's linear range of 0 to 1, of a pixel that `color` reaches.
+    pub(super) fn apply(&self, color: Color) -> Color {
+        self.apply_exposed(color * self.exposure)
+    }
+
+    /// Like [`Display::apply`], for `color` that's already been exposed.
+    pub(super) fn apply_exposed(&self, color: Color) -> Color {
+        self.tone_map.apply(color * self.gains)
+    }
+}
+
+/// Hill's matrix from linear sRGB to the space the ACES fit works in.
+const ACES_INPUT: [[f32; 3]; 3] = [
+    [0.597_19, 0.354_58, 0.048_23],
+    [0.076_00, 0.908_34, 0.015_66],
+    [0.028_40, 0.133_83, 0.837_77],
+];
+/// Hill's matrix back to linear sRGB from the space the ACES fit works in.
+const ACES_OUTPUT: [[f32; 3]; 3] = [
+    [1.604_75, -0.531_08, -0.073_67],
+    [-0.102_08, 1.108_13, -0.006_05],
+    [-0.003_27, -0.072_76, 1.076_02],
+];
+/// The matrix AgX insets linear sRGB by, so that bright saturated colors head towards white.
+const AGX_INSET: [[f32; 3]; 3] = [
+    [0.842_479_06, 0.078_433_6, 0.079_223_745],
+    [0.042_328_242, 0.878_468_6, 0.079_166_13],
+    [0.042_375_655, 0.078_433_6, 0.879_143],
+];
+/// The inverse of [`AGX_INSET`].
+const AGX_OUTSET: [[f32; 3]; 3] = [
+    [1.196_879, -0.098_020_88, -0.099_029_74],
+    [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+    [-0.052_971_635, -0.098_043_45, 1.151_073_7],
+];
+
+/// `color` with `f` applied to each of its channels.
+fn per_channel(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(color.x()), f(color.y()), f(color.z()))
+}
+
+/// The product of the matrix with the given `rows` and `color`.
+fn multiply(rows: &[[f32; 3]; 3], color: Color) -> Color {
+    let [r, g, b] = rows.map(|[a, b, c]| a * color.x() + b * color.y() + c * color.z());
+    Color::new(r, g, b)
+}
+
+/// Hable's filmic curve, before it's scaled for its white point to map to 1.
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Scales `color` so that its luminance becomes `f` of what it was.
+fn scale_luminance(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    let luminance = luminance(color);
+    if luminance <= 0.0 {
+        return Color::default();
+    }
+    color * (f(luminance) / luminance)
+}
+
+fn luminance(color: Color) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// The linear sRGB color of the light a black body at `temperature` kelvin gives off, of
+/// arbitrary brightness.
+fn blackbody_rgb(temperature: f32) -> Color {
+    // the second radiation constant, in nanometer kelvin.
+    const C2: f64 = 1.438_777e7;
+    let temperature = f64::from(temperature.max(1.0));
+    let xyz = (360..=830)
+        .step_by(5)
+        .fold(Color::default(), |xyz, lambda| {
+            let lambda = f64::from(lambda);
+            let radiance = lambda.powi(-5) / (C2 / (lambda * temperature)).exp_m1();
+            // scaled up so that the radiance doesn't underflow a float.
+            xyz + cie_xyz(lambda as f32) * (radiance * 1e15) as f32
+        });
+    xyz_to_linear_srgb(xyz)
+}
@@ -10,7 +10,9 @@ use crate::{
 };
 
 mod sphere;
+mod transform;
 pub use sphere::Sphere;
+pub use transform::{Transform, Transformed};
 
 pub struct HitRecord<'a> {
     pub point: Point3,
@@ -18,6 +20,9 @@ pub struct HitRecord<'a> {
     pub material: &'a dyn Material,
     pub t: f32,
     pub front_face: bool,
+    /// Which object in the world was hit, counting from 1 in the outermost list holding it, or 0
+    /// if it isn't in a list.
+    pub object_id: u32,
 }
 
 impl HitRecord<'_> {
@@ -31,11 +36,11 @@ impl HitRecord<'_> {
     }
 }
 
-pub trait Hittable: Send + Sync {
-    fn hit(&self, r: &Ray, ray_t: &Range<f32>) -> Option<HitRecord>;
+pub trait Hittable: std::fmt::Debug + Send + Sync {
+    fn hit(&self, r: &Ray, ray_t: &Range<f32>) -> Option<HitRecord<'_>>;
 }
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct HittableList<'a> {
     objects: Vec<&'a (dyn Hittable + Sync + Send)>,
 }
@@ -51,12 +56,13 @@ impl<'a> HittableList<'a> {
 }
 
 impl Hittable for HittableList<'_> {
-    fn hit(&self, r: &Ray, ray_t: &Range<f32>) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: &Range<f32>) -> Option<HitRecord<'_>> {
         let mut closest_so_far = ray_t.end;
         let mut rec = None;
-        for object in self.objects.iter() {
-            if let Some(record) = object.hit(r, &(ray_t.start..closest_so_far)) {
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(mut record) = object.hit(r, &(ray_t.start..closest_so_far)) {
                 closest_so_far = record.t;
+                record.object_id = index as u32 + 1;
                 rec = Some(record);
             }
         }
@@ -24,7 +24,7 @@ impl<'a> Sphere<'a> {
 }
 
 impl Hittable for Sphere<'_> {
-    fn hit(&self, r: &crate::ray::Ray, ray_t: &std::ops::Range<f32>) -> Option<HitRecord> {
+    fn hit(&self, r: &crate::ray::Ray, ray_t: &std::ops::Range<f32>) -> Option<HitRecord<'_>> {
         let oc = r.origin() - self.center;
         let a = r.direction().len_squared();
         let half_b = oc.dot(&r.direction());
@@ -54,6 +54,7 @@ impl Hittable for Sphere<'_> {
             t: root,
             front_face: false,
             material: self.material,
+            object_id: 0,
         };
         let outward_normal = (point - self.center) * self.radius_recip;
         hit_record.set_face_normal(r, outward_normal);
@@ -0,0 +1,87 @@
+use crate::{
+    geometry::{HitRecord, Hittable},
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// A change of an object's position, orientation and size.
+#[derive(Debug, Copy, Clone)]
+pub struct Transform {
+    pub translation: Vec3,
+    /// Angles in degrees to turn about the x axis, then the y axis, then the z axis.
+    pub rotation: Vec3,
+    /// How many times larger to make the object, which has to be positive.
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::default(),
+            rotation: Vec3::default(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    /// The rows of the matrix turning objects by the transform's rotation.
+    fn rotation_matrix(&self) -> [Vec3; 3] {
+        let (sx, cx) = self.rotation.x().to_radians().sin_cos();
+        let (sy, cy) = self.rotation.y().to_radians().sin_cos();
+        let (sz, cz) = self.rotation.z().to_radians().sin_cos();
+
+        // the product of the rotations about z, y and x, in that order.
+        [
+            Vec3::new(cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx),
+            Vec3::new(sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx),
+            Vec3::new(-sy, cy * sx, cy * cx),
+        ]
+    }
+}
+
+/// An object moved, turned and scaled from where it's defined, without changing the object
+/// itself.
+#[derive(Debug)]
+pub struct Transformed<'a> {
+    object: &'a (dyn Hittable + Sync + Send),
+    transform: Transform,
+    rotation: [Vec3; 3],
+}
+
+impl<'a> Transformed<'a> {
+    pub fn new(object: &'a (dyn Hittable + Sync + Send), transform: Transform) -> Self {
+        Self {
+            object,
+            transform,
+            rotation: transform.rotation_matrix(),
+        }
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        let [x, y, z] = self.rotation;
+        Vec3::new(x.dot(&v), y.dot(&v), z.dot(&v))
+    }
+
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        let [x, y, z] = self.rotation;
+        v.x() * x + v.y() * y + v.z() * z
+    }
+}
+
+impl Hittable for Transformed<'_> {
+    fn hit(&self, r: &Ray, ray_t: &std::ops::Range<f32>) -> Option<HitRecord<'_>> {
+        // scaling the direction along with the origin keeps distances along the ray the same.
+        let scale = self.transform.scale.recip();
+        let ray = Ray::new(
+            self.to_object(r.origin() - self.transform.translation) * scale,
+            self.to_object(r.direction()) * scale,
+        );
+
+        let mut hit_record = self.object.hit(&ray, ray_t)?;
+        hit_record.point =
+            self.to_world(hit_record.point) * self.transform.scale + self.transform.translation;
+        hit_record.normal = self.to_world(hit_record.normal);
+        Some(hit_record)
+    }
+}
@@ -0,0 +1,227 @@
+use std::f32::consts::TAU;
+
+use crate::{
+    sampler::Sampler,
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Light arriving at a point from a light source.
+#[derive(Debug)]
+pub struct LightSample {
+    /// Unit vector pointing from the shaded point towards the light.
+    pub direction: Vec3,
+    /// Distance to the light, or infinity for lights that are infinitely far away.
+    pub distance: f32,
+    /// Radiance arriving along `direction`, assuming nothing blocks it.
+    pub radiance: Color,
+}
+
+/// A light source that can't be hit by rays, and so must be sampled explicitly by casting shadow
+/// rays towards it.
+pub trait Light: std::fmt::Debug + Send + Sync {
+    /// Samples the light arriving at `point`, or `None` if the light doesn't illuminate it.
+    fn sample(&self, point: &Point3, sampler: &mut dyn Sampler) -> Option<LightSample>;
+}
+
+/// How the intensity of a light diminishes with distance.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum Falloff {
+    /// Physically correct `1 / d²` falloff.
+    #[default]
+    InverseSquare,
+    /// A gentler `1 / d` falloff.
+    Linear,
+    /// Constant intensity at any distance.
+    None,
+}
+
+impl Falloff {
+    fn attenuation(&self, distance: f32) -> f32 {
+        match self {
+            Falloff::InverseSquare => (distance * distance).recip(),
+            Falloff::Linear => distance.recip(),
+            Falloff::None => 1.0,
+        }
+    }
+}
+
+/// A light emitting equally in all directions from a single point.
+#[derive(Debug)]
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+    falloff: Falloff,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+            falloff: Falloff::default(),
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.len();
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity * self.falloff.attenuation(distance),
+        })
+    }
+}
+
+/// A point light emitting in a cone, with a soft edge between an inner cone at full intensity
+/// and an outer cone beyond which it emits nothing.
+#[derive(Debug)]
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_inner: f32,
+    cos_outer: f32,
+    falloff: Falloff,
+}
+
+impl SpotLight {
+    /// Creates a spot light at `position` shining towards `target`.  The angles are the full
+    /// opening angles of the inner and outer cones, in degrees.
+    pub fn new(
+        position: Point3,
+        target: Point3,
+        intensity: Color,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        Self {
+            position,
+            direction: (target - position).normalize(),
+            intensity,
+            cos_inner: (0.5 * inner_angle).to_radians().cos(),
+            cos_outer: (0.5 * outer_angle).to_radians().cos(),
+            falloff: Falloff::default(),
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    fn cone_attenuation(&self, cos_theta: f32) -> f32 {
+        if cos_theta >= self.cos_inner {
+            1.0
+        } else if cos_theta <= self.cos_outer {
+            0.0
+        } else {
+            let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.len();
+        let direction = to_light / distance;
+
+        let cone = self.cone_attenuation(-direction.dot(&self.direction));
+        if cone <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * (cone * self.falloff.attenuation(distance)),
+        })
+    }
+}
+
+/// A light infinitely far away, such as the sun.  With a non-zero angular diameter, it covers a
+/// small disk of the sky and casts soft shadows.
+#[derive(Debug)]
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+    cos_max: f32,
+}
+
+impl DirectionalLight {
+    /// Creates a light shining along `direction` (i.e. from `-direction`).
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self {
+            direction: -direction.normalize(),
+            irradiance,
+            cos_max: 1.0,
+        }
+    }
+
+    /// Sets the angular diameter of the light's disk, in degrees.  The sun is about half a degree
+    /// across.
+    pub fn with_angular_diameter(mut self, angular_diameter: f32) -> Self {
+        self.cos_max = (0.5 * angular_diameter).to_radians().cos();
+        self
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Point3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let direction = if self.cos_max >= 1.0 {
+            self.direction
+        } else {
+            sample_cone(&self.direction, self.cos_max, sampler.get_2d())
+        };
+
+        Some(LightSample {
+            direction,
+            distance: f32::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
+
+/// Uniformly samples a direction within the cone around `axis` whose half-angle has cosine
+/// `cos_max`, given a pair of uniform values `(u, v)` in `[0, 1)`.
+pub fn sample_cone(axis: &Vec3, cos_max: f32, (u, v): (f32, f32)) -> Vec3 {
+    let cos_theta = 1.0 - u * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let (sin_phi, cos_phi) = (TAU * v).sin_cos();
+
+    let helper = if axis.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let u = axis.cross(&helper).normalize();
+    let v = axis.cross(&u);
+
+    sin_theta * cos_phi * u + sin_theta * sin_phi * v + cos_theta * *axis
+}
+
+#[derive(Debug, Default)]
+pub struct LightList<'a> {
+    lights: Vec<&'a dyn Light>,
+}
+
+impl<'a> LightList<'a> {
+    pub fn add(&mut self, light: &'a dyn Light) {
+        self.lights.push(light)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a dyn Light> + '_ {
+        self.lights.iter().copied()
+    }
+}
@@ -1,45 +1,793 @@
 use std::{
-    io::{BufWriter, Result, Write},
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Result, Write},
     iter,
-    path::PathBuf,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
-use camera::CameraBuilder;
+use camera::{
+    AdaptiveSampling, Aov, AovFormat, Aovs, Aperture, ApertureMask, ApertureShape, Camera,
+    CameraBuilder, Checkpoint, ContactSheet, Coordinator, Crop, CropWindow, Denoise, Eye, Filter,
+    FisheyeMapping, LensSystem, OutputTransform, PhysicalCamera, PpmTileOutput, Progressive,
+    Projection, Stereo, StereoLayout, TileOrder, Tiles, ToneMap, Worker,
+};
 use clap::{Parser, ValueEnum};
-use material::{Dielectric, Material, Metal};
-use rand::{thread_rng, Rng};
+use material::{Dielectric, Ior, Material, Metal, Subsurface, ThicknessGradient, ThinFilm};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sampler::SamplerKind;
 use vec3::{Color, Vec3};
 
 use crate::{
-    geometry::{HittableList, Sphere},
+    animation::{CameraAnimation, Interpolation, Keyframe, Track, TransformAnimation, Turntable},
+    geometry::{HittableList, Sphere, Transformed},
+    light::{DirectionalLight, Falloff, LightList, PointLight, SpotLight},
     material::Lambertian,
+    sky::{Background, PreethamSky},
     vec3::Point3,
 };
 
+pub mod animation;
 pub mod camera;
 pub mod geometry;
+pub mod light;
 mod material;
 pub mod ray;
+pub mod sampler;
+pub mod sky;
+pub mod spectrum;
 mod util;
 pub mod vec3;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    #[arg(short, long)]
-    scene: Scene,
+    #[arg(short, long, required_unless_present = "worker")]
+    scene: Option<Scene>,
+
+    /// Trace paths at sampled wavelengths rather than in RGB.
+    #[arg(long)]
+    spectral: bool,
+
+    /// Elevation of the sun above the horizon in degrees, for scenes lit by daylight.
+    #[arg(long, default_value_t = 35.0, allow_negative_numbers = true)]
+    sun_elevation: f32,
+
+    /// Azimuth of the sun in degrees, measured from -z towards +x, for scenes lit by daylight.
+    #[arg(long, default_value_t = 60.0, allow_negative_numbers = true)]
+    sun_azimuth: f32,
+
+    /// Haziness of the atmosphere, from 2 (very clear) to 10 (hazy), for scenes lit by daylight.
+    #[arg(long, default_value_t = 3.0)]
+    turbidity: f32,
+
+    /// Projection to render with, overriding the scene's perspective camera.
+    #[arg(long)]
+    projection: Option<ProjectionArg>,
+
+    /// Height of the scene covered by an orthographic projection.
+    #[arg(long, default_value_t = 4.0)]
+    view_height: f32,
+
+    /// Field of view of a fisheye projection across the width of the image, in degrees.
+    #[arg(long, default_value_t = 180.0)]
+    fisheye_fov: f32,
+
+    /// Mapping of a fisheye projection.
+    #[arg(long, default_value = "equidistant")]
+    fisheye_mapping: FisheyeMappingArg,
+
+    /// Render a stereo pair for VR, arranged in the given layout.
+    #[arg(long)]
+    stereo: Option<StereoArg>,
+
+    /// Distance between the eyes of a stereo pair, in scene units.
+    #[arg(long, default_value_t = 0.064)]
+    interpupillary_distance: f32,
+
+    /// Distance at which the eyes of a stereo pair converge, in scene units.
+    #[arg(long, default_value_t = 10.0)]
+    convergence_distance: f32,
+
+    /// Focal length of the lens in millimeters.  Describes the camera physically, replacing the
+    /// scene's field of view and defocus blur with ones derived from the lens and sensor.
+    #[arg(long, value_parser = parse_positive)]
+    focal_length: Option<f32>,
+
+    /// Size of the camera's sensor in millimeters, as WIDTHxHEIGHT.
+    #[arg(long, default_value = "36x24", value_parser = parse_sensor_size)]
+    sensor_size: (f32, f32),
+
+    /// Aperture of a physically described camera, as an f-number.
+    #[arg(long, default_value_t = 16.0, value_parser = parse_positive)]
+    f_number: f32,
+
+    /// Shutter time of a physically described camera, in seconds.
+    #[arg(long, default_value_t = 0.01, value_parser = parse_positive)]
+    shutter_time: f32,
+
+    /// Sensitivity of a physically described camera.
+    #[arg(long, default_value_t = 100.0, value_parser = parse_positive)]
+    iso: f32,
+
+    /// Number of straight blades forming the lens's aperture, giving out-of-focus highlights a
+    /// polygonal shape.  The aperture is circular if neither this nor a mask is given.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(3..))]
+    aperture_blades: Option<u32>,
+
+    /// Rotation of the aperture's blades, in degrees.
+    #[arg(long, default_value_t = 0.0)]
+    aperture_rotation: f32,
+
+    /// A PGM or PPM image giving the shape of the lens's aperture.  Takes precedence over
+    /// `--aperture-blades`.
+    #[arg(long)]
+    aperture_mask: Option<PathBuf>,
+
+    /// How strongly the lens barrel vignettes the aperture towards the edges of the image, giving
+    /// out-of-focus highlights a cat's eye shape.
+    #[arg(long, default_value_t = 0.0)]
+    cat_eye: f32,
+
+    /// Horizontal squeeze of an anamorphic lens, stretching out-of-focus highlights into ovals.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    anamorphic_squeeze: f32,
+
+    /// A lens prescription to trace camera rays through, in pbrt's format (see `lenses/`).  Its
+    /// film has the diagonal of `--sensor-size`, and scene units are taken to be meters.
+    #[arg(long)]
+    lens_file: Option<PathBuf>,
+
+    /// Stops the aperture of `--lens-file` down to this diameter, in millimeters.
+    #[arg(long)]
+    lens_aperture_diameter: Option<f32>,
+
+    /// Where the values for the renderer's random choices come from.  Low discrepancy samplers
+    /// reach the same quality with fewer samples per pixel.
+    #[arg(long, value_enum, default_value_t = SamplerArg::Independent)]
+    sampler: SamplerArg,
+
+    /// The filter that weighs samples by their position around each pixel.
+    #[arg(long, value_enum, default_value_t = FilterArg::Box)]
+    filter: FilterArg,
+
+    /// How far the filter reaches from the center of each pixel, in pixels.  Defaults to 0.5 for
+    /// the box filter, 1 for the tent, 1.5 for the Gaussian, 2 for Mitchell and Blackman-Harris,
+    /// and 3 for Lanczos.  The Gaussian's standard deviation and Lanczos's number of lobes follow
+    /// from it.
+    #[arg(long, value_parser = parse_positive)]
+    filter_radius: Option<f32>,
+
+    /// Sample each pixel only until its estimated error is small enough, rather than taking the
+    /// same number of samples everywhere.
+    #[arg(long)]
+    adaptive: bool,
+
+    /// The fewest samples adaptive sampling takes for a pixel.
+    #[arg(long, default_value_t = 16)]
+    min_samples: u32,
+
+    /// The most samples adaptive sampling takes for a pixel.  Defaults to the scene's samples per
+    /// pixel.
+    #[arg(long)]
+    max_samples: Option<u32>,
+
+    /// The estimated error in a pixel's displayed brightness, from 0 to 1, at which adaptive
+    /// sampling stops sampling it.
+    #[arg(long)]
+    max_error: Option<f32>,
+
+    /// Also write an image of how many samples each pixel got to this path.
+    #[arg(long)]
+    sample_heatmap: Option<PathBuf>,
+
+    /// Render in passes over the whole image, replacing the output with a snapshot of the image
+    /// so far every so often, so that long renders can be previewed and stopped early.
+    #[arg(long)]
+    progressive: bool,
+
+    /// How many samples to take for every pixel in each pass of a progressive render.
+    #[arg(long, default_value_t = 4)]
+    samples_per_pass: u32,
+
+    /// How long to wait at least between snapshots of a progressive render, as a number of
+    /// seconds or with a unit (`s`, `m` or `h`).
+    #[arg(long, default_value = "10", value_parser = parse_duration)]
+    snapshot_interval: Duration,
+
+    /// Keep taking passes of samples until this much time has passed, as a number of seconds or
+    /// with a unit (`s`, `m` or `h`), instead of stopping at the scene's samples per pixel.
+    #[arg(long, value_parser = parse_duration)]
+    time_limit: Option<Duration>,
+
+    /// Render only the pixels from X0,Y0 up to but not including X1,Y1, counting from the top
+    /// left corner, as an image of just those pixels.
+    #[arg(long, value_parser = parse_crop_pixels)]
+    crop: Option<CropWindow>,
+
+    /// Render only the region from X0,Y0 to X1,Y1, as fractions of the image's width and height
+    /// from 0 to 1.
+    #[arg(long, value_parser = parse_crop_normalized, conflicts_with = "crop")]
+    crop_normalized: Option<CropWindow>,
+
+    /// Write cropped renders at the full size of the image, black outside the crop.
+    #[arg(long)]
+    crop_full_frame: bool,
+
+    /// Hand out the image's tiles to worker processes that connect to this address, such as
+    /// 0.0.0.0:7878, rather than rendering it here.
+    #[arg(long, conflicts_with_all = ["progressive", "time_limit", "checkpoint", "write_tiles"])]
+    coordinate: Option<String>,
+
+    /// How long a coordinator waits for a worker to answer before handing its tile to another,
+    /// as a number of seconds or with a unit (`s`, `m` or `h`).  Has to be longer than a worker
+    /// takes to render a tile.
+    #[arg(long, default_value = "10m", value_parser = parse_duration, requires = "coordinate")]
+    worker_timeout: Duration,
+
+    /// Render tiles for the coordinator at this address, of the scene it was started with,
+    /// until it's finished.  Everything else on the command line is ignored.
+    #[arg(long)]
+    worker: Option<String>,
+
+    /// Render the frames of the scene's animation from START to END, as START..END, or START..=END
+    /// to include the last, writing each to the output with its number added to the name.
+    #[arg(long, value_parser = parse_frames, conflicts_with = "coordinate")]
+    frames: Option<RangeInclusive<u32>>,
+
+    /// Circle the camera around the point it looks at over this many frames, starting from the
+    /// scene's view, writing each to the output with its number added to the name.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "frames")]
+    turntable: Option<u32>,
+
+    /// How many degrees a turntable camera rises above and sinks below the scene's view over
+    /// the turn.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    elevation_sweep: f32,
+
+    /// Write the frames of an animation or turntable laid out in a grid in a single image,
+    /// rather than an image for each.
+    #[arg(long, conflicts_with_all = ["progressive", "write_tiles", "coordinate"])]
+    contact_sheet: bool,
+
+    /// The frame of the animation being rendered, which is set for each frame rather than on the
+    /// command line.
+    #[arg(skip)]
+    frame: u32,
+
+    /// Render in square tiles this many pixels across, each taken on by a single thread.
+    /// Defaults to 32 when rendering in tiles.
+    #[arg(long)]
+    tile_size: Option<u32>,
+
+    /// The order to render tiles in.  Rendering in tiles unless told otherwise.
+    #[arg(long, value_enum)]
+    tile_order: Option<TileOrderArg>,
+
+    /// Write each tile to the output, as a binary PPM, as soon as it's finished, so that the
+    /// image fills in as it renders.
+    #[arg(long, conflicts_with = "progressive")]
+    write_tiles: bool,
+
+    /// Also render these buffers for compositing, separated by commas.
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with_all = ["coordinate", "contact_sheet"])]
+    aovs: Vec<AovArg>,
+
+    /// Where to write the buffers `--aovs` asks for: as the layers of a single OpenEXR file if
+    /// the path ends in `.exr`, and otherwise as a PPM for each, with the buffer's name added to
+    /// the path.  Defaults to the output path.
+    #[arg(long, requires = "aovs")]
+    aov_output: Option<PathBuf>,
+
+    /// Smooth away the noise left in the finished image, guided by the albedo, normals and depth
+    /// of what each pixel sees.
+    #[arg(long, conflicts_with_all = ["write_tiles", "coordinate"])]
+    denoise: bool,
+
+    /// How many pixels away the denoiser looks for neighbours to average each pixel with.
+    #[arg(long, default_value_t = 6)]
+    denoise_radius: u32,
+
+    /// How readily the denoiser averages pixels of different colors.  Lower strengths keep more
+    /// detail, and more noise.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_non_negative)]
+    denoise_strength: f32,
+
+    /// How many stops to brighten the image by, on top of any physical camera's exposure.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f32,
+
+    /// The color temperature, in kelvin, of light that's shown as white.
+    #[arg(long)]
+    white_balance: Option<f32>,
+
+    /// How light too bright for the display is brought into its range.
+    #[arg(long, value_enum, default_value_t = ToneMapArg::Clamp)]
+    tone_map: ToneMapArg,
+
+    /// The luminance the extended Reinhard tone map shows as white.
+    #[arg(long, default_value_t = 4.0)]
+    white_point: f32,
+
+    /// Seeds the renderer's random choices, and those of scenes laid out at random.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Save the samples taken so far to this file every so often, so that the render can be
+    /// resumed if it's stopped.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// How long to wait at least between saving checkpoints, as a number of seconds or with a
+    /// unit (`s`, `m` or `h`).
+    #[arg(long, default_value = "60", value_parser = parse_duration)]
+    checkpoint_interval: Duration,
+
+    /// Carry on from the samples saved in `--checkpoint`, which has to be of the same scene,
+    /// seen the same way, with the same `--aovs` and `--denoise`.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// A point in the scene to focus on, as X,Y,Z.  It has to be in front of the camera.
+    #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+    focus_point: Option<Point3>,
+
+    /// Focus on whatever is seen through the pixel at I,J (counting from the top left corner).
+    /// Takes precedence over `--focus-point`.
+    #[arg(long, value_parser = parse_pixel)]
+    focus_pixel: Option<(u64, u64)>,
+}
+
+fn parse_sensor_size(value: &str) -> std::result::Result<(f32, f32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {value}"))?;
+    Ok((parse_positive(width)?, parse_positive(height)?))
+}
+
+fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(split) => value.split_at(split),
+        None => (value, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(format!(
+                "expected a duration such as 90, 90s, 15m or 2h, got {value}"
+            ))
+        }
+    };
+    let number = number.trim().parse::<f32>().map_err(|e| e.to_string())?;
+    Duration::try_from_secs_f32(number * seconds).map_err(|e| e.to_string())
+}
+
+fn parse_positive(value: &str) -> std::result::Result<f32, String> {
+    match value.trim().parse::<f32>().map_err(|e| e.to_string())? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("expected a positive number, got {value}")),
+    }
+}
+
+fn parse_non_negative(value: &str) -> std::result::Result<f32, String> {
+    match value.trim().parse::<f32>().map_err(|e| e.to_string())? {
+        number if number >= 0.0 => Ok(number),
+        _ => Err(format!("expected a number no less than 0, got {value}")),
+    }
+}
+
+fn parse_point(value: &str) -> std::result::Result<Point3, String> {
+    let coordinates = value
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    match coordinates[..] {
+        [x, y, z] => Ok(Point3::new(x, y, z)),
+        _ => Err(format!("expected X,Y,Z, got {value}")),
+    }
+}
+
+fn parse_frames(value: &str) -> std::result::Result<RangeInclusive<u32>, String> {
+    let parse = |v: &str| v.trim().parse::<u32>().map_err(|e| e.to_string());
+    let (start, end) = match value.split_once("..") {
+        Some((start, end)) => match end.strip_prefix('=') {
+            Some(end) => (parse(start)?, Some(parse(end)?)),
+            None => (parse(start)?, parse(end)?.checked_sub(1)),
+        },
+        None => (parse(value)?, Some(parse(value)?)),
+    };
+    match end {
+        Some(end) if start <= end => Ok(start..=end),
+        _ => Err(format!(
+            "expected START..END with START before END, got {value}"
+        )),
+    }
+}
+
+fn parse_crop_pixels(value: &str) -> std::result::Result<CropWindow, String> {
+    match parse_list::<u64>(value)?[..] {
+        [x_min, y_min, x_max, y_max] if x_min < x_max && y_min < y_max => Ok(CropWindow::Pixels {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        }),
+        _ => Err(format!(
+            "expected X0,Y0,X1,Y1 with X0 < X1 and Y0 < Y1, got {value}"
+        )),
+    }
+}
+
+fn parse_crop_normalized(value: &str) -> std::result::Result<CropWindow, String> {
+    match parse_list::<f32>(value)?[..] {
+        [x_min, y_min, x_max, y_max] if x_min < x_max && y_min < y_max => {
+            Ok(CropWindow::Normalized {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            })
+        }
+        _ => Err(format!(
+            "expected X0,Y0,X1,Y1 with X0 < X1 and Y0 < Y1, got {value}"
+        )),
+    }
+}
+
+fn parse_list<T: std::str::FromStr<Err: std::fmt::Display>>(
+    value: &str,
+) -> std::result::Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|v| v.trim().parse::<T>().map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn parse_pixel(value: &str) -> std::result::Result<(u64, u64), String> {
+    let (i, j) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected I,J, got {value}"))?;
+    let parse = |v: &str| v.trim().parse::<u64>().map_err(|e| e.to_string());
+    Ok((parse(i)?, parse(j)?))
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum SamplerArg {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum FilterArg {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+    Lanczos,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum TileOrderArg {
+    Scanline,
+    /// Spiralling out from the center of the image.
+    Spiral,
+    Hilbert,
+    Morton,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum AovArg {
+    Albedo,
+    Normal,
+    Depth,
+    Position,
+    ObjectId,
+    MaterialId,
+    Direct,
+    Indirect,
+    Emission,
+    /// The direct light from each light, in a buffer of its own.
+    Lights,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ToneMapArg {
+    /// Clip each channel at white.
+    Clamp,
+    Reinhard,
+    /// Reinhard, reaching white at `--white-point`.
+    ExtendedReinhard,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// A fit of ACES.
+    Aces,
+    Agx,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum StereoArg {
+    SideBySide,
+    TopBottom,
+    /// Each eye in its own file, with `-left` and `-right` added to the output's name.
+    Separate,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ProjectionArg {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum FisheyeMappingArg {
+    Equidistant,
+    Equisolid,
+}
+
+impl Args {
+    /// The reconstruction filter given on the command line.
+    fn filter(&self) -> Filter {
+        let radius = |default| self.filter_radius.unwrap_or(default);
+        match self.filter {
+            FilterArg::Box => Filter::Box {
+                radius: radius(0.5),
+            },
+            FilterArg::Tent => Filter::Tent {
+                radius: radius(1.0),
+            },
+            FilterArg::Gaussian => {
+                let radius = radius(1.5);
+                Filter::Gaussian {
+                    radius,
+                    sigma: radius / 3.0,
+                }
+            }
+            FilterArg::Mitchell => Filter::Mitchell {
+                radius: radius(2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            FilterArg::BlackmanHarris => Filter::BlackmanHarris {
+                radius: radius(2.0),
+            },
+            FilterArg::Lanczos => {
+                let radius = radius(3.0);
+                Filter::Lanczos {
+                    radius,
+                    tau: radius,
+                }
+            }
+        }
+    }
+
+    /// Applies the camera settings given on the command line on top of a scene's camera, which
+    /// views `world`.
+    fn configure_camera(
+        &self,
+        builder: CameraBuilder,
+        world: &HittableList,
+    ) -> Result<CameraBuilder> {
+        let builder = match self.turntable {
+            Some(frames) => Turntable::new(frames)
+                .with_elevation_sweep(self.elevation_sweep)
+                .apply(builder, self.frame),
+            None => builder,
+        };
+        let mut builder = builder
+            .with_spectral_rendering(self.spectral)
+            .with_seed(self.seed)
+            .with_sampler(match self.sampler {
+                SamplerArg::Independent => SamplerKind::Independent,
+                SamplerArg::Stratified => SamplerKind::Stratified,
+                SamplerArg::Halton => SamplerKind::Halton,
+                SamplerArg::Sobol => SamplerKind::Sobol,
+                SamplerArg::BlueNoise => SamplerKind::BlueNoise,
+            })
+            .with_filter(self.filter());
+
+        if self.adaptive {
+            let max_samples = self
+                .max_samples
+                .or(builder.samples_per_pixel.map(u32::from))
+                .unwrap_or(self.min_samples);
+            let mut adaptive = AdaptiveSampling::new(self.min_samples, max_samples);
+            if let Some(max_error) = self.max_error {
+                adaptive = adaptive.with_max_error(max_error);
+            }
+            builder = builder.with_adaptive_sampling(adaptive);
+        }
+        if let Some(path) = &self.sample_heatmap {
+            builder = builder.with_sample_heatmap(path.clone());
+        }
+        if let Some(time_limit) = self.time_limit {
+            builder = builder.with_time_limit(time_limit);
+        }
+        if let Some(window) = self.crop.or(self.crop_normalized) {
+            builder = builder.with_crop(Crop::new(window).with_full_frame(self.crop_full_frame));
+        }
+        if self.tile_size.is_some() || self.tile_order.is_some() || self.write_tiles {
+            let order = match self.tile_order.unwrap_or(TileOrderArg::Hilbert) {
+                TileOrderArg::Scanline => TileOrder::Scanline,
+                TileOrderArg::Spiral => TileOrder::Spiral,
+                TileOrderArg::Hilbert => TileOrder::Hilbert,
+                TileOrderArg::Morton => TileOrder::Morton,
+            };
+            builder =
+                builder.with_tiles(Tiles::new(self.tile_size.unwrap_or(32)).with_order(order));
+        }
+        if let (true, Some(output)) = (self.write_tiles, &self.output) {
+            builder = builder.with_tile_output(Arc::new(PpmTileOutput::new(output.clone())));
+        }
+        if let (false, Some(path)) = (
+            self.aovs.is_empty(),
+            self.aov_output.as_ref().or(self.output.as_ref()),
+        ) {
+            let buffers = self
+                .aovs
+                .iter()
+                .map(|aov| match aov {
+                    AovArg::Albedo => Aov::Albedo,
+                    AovArg::Normal => Aov::Normal,
+                    AovArg::Depth => Aov::Depth,
+                    AovArg::Position => Aov::Position,
+                    AovArg::ObjectId => Aov::ObjectId,
+                    AovArg::MaterialId => Aov::MaterialId,
+                    AovArg::Direct => Aov::Direct,
+                    AovArg::Indirect => Aov::Indirect,
+                    AovArg::Emission => Aov::Emission,
+                    AovArg::Lights => Aov::Lights,
+                })
+                .collect();
+            let format = match path.extension() {
+                Some(extension) if extension.eq_ignore_ascii_case("exr") => AovFormat::Exr,
+                _ => AovFormat::Ppm,
+            };
+            builder = builder.with_aovs(Aovs::new(buffers, path.clone()).with_format(format));
+        }
+        if self.denoise {
+            builder = builder.with_denoise(
+                Denoise::new()
+                    .with_radius(self.denoise_radius)
+                    .with_strength(self.denoise_strength),
+            );
+        }
+        let tone_map = match self.tone_map {
+            ToneMapArg::Clamp => ToneMap::Clamp,
+            ToneMapArg::Reinhard => ToneMap::Reinhard,
+            ToneMapArg::ExtendedReinhard => ToneMap::ExtendedReinhard {
+                white: self.white_point,
+            },
+            ToneMapArg::Hable => ToneMap::Hable,
+            ToneMapArg::Aces => ToneMap::Aces,
+            ToneMapArg::Agx => ToneMap::Agx,
+        };
+        let mut output = OutputTransform::new()
+            .with_exposure(self.exposure)
+            .with_tone_map(tone_map);
+        if let Some(temperature) = self.white_balance {
+            output = output.with_white_balance(temperature);
+        }
+        builder = builder.with_output_transform(output);
+        if let Some(path) = &self.checkpoint {
+            builder = builder.with_checkpoint(
+                Checkpoint::new(path.clone())
+                    .with_interval(self.checkpoint_interval)
+                    .with_resume(self.resume),
+            );
+        }
+        if let (true, Some(output)) = (self.progressive, &self.output) {
+            builder = builder.with_progressive(
+                Progressive::new(output.clone())
+                    .with_samples_per_pass(self.samples_per_pass)
+                    .with_snapshot_interval(self.snapshot_interval),
+            );
+        }
+
+        let shape = match (&self.aperture_mask, self.aperture_blades) {
+            (Some(path), _) => Some(ApertureShape::Mask(ApertureMask::load(path)?)),
+            (None, Some(blades)) => Some(ApertureShape::Polygon {
+                blades,
+                rotation: self.aperture_rotation,
+            }),
+            (None, None) => None,
+        };
+        if shape.is_some() || self.cat_eye > 0.0 || self.anamorphic_squeeze != 1.0 {
+            builder = builder.with_aperture(
+                Aperture::new(shape.unwrap_or_default())
+                    .with_cat_eye(self.cat_eye)
+                    .with_anamorphic_squeeze(self.anamorphic_squeeze),
+            );
+        }
+
+        if let Some(focal_length) = self.focal_length {
+            let (sensor_width, sensor_height) = self.sensor_size;
+            builder = builder.with_physical_camera(
+                PhysicalCamera::new(focal_length)?
+                    .with_sensor(sensor_width, sensor_height)?
+                    .with_f_number(self.f_number)?
+                    .with_shutter_time(self.shutter_time)?
+                    .with_iso(self.iso)?,
+            );
+        }
+
+        if let Some(path) = &self.lens_file {
+            let (sensor_width, sensor_height) = self.sensor_size;
+            let mut lens =
+                LensSystem::load(path)?.with_film_diagonal(sensor_width.hypot(sensor_height));
+            if let Some(diameter) = self.lens_aperture_diameter {
+                lens = lens.with_aperture_diameter(diameter);
+            }
+            builder = builder.with_lens_system(lens);
+        }
+
+        if let Some(layout) = self.stereo {
+            let stereo = Stereo::new(self.interpupillary_distance, self.convergence_distance);
+            builder = builder.with_stereo(match layout {
+                StereoArg::TopBottom => stereo.with_layout(StereoLayout::TopBottom),
+                StereoArg::SideBySide | StereoArg::Separate => stereo,
+            });
+        }
+
+        builder = match self.projection {
+            None => builder,
+            Some(ProjectionArg::Perspective) => builder.with_projection(Projection::Perspective),
+            Some(ProjectionArg::Orthographic) => {
+                builder.with_projection(Projection::Orthographic {
+                    view_height: self.view_height,
+                })
+            }
+            Some(ProjectionArg::Fisheye) => builder.with_projection(Projection::Fisheye {
+                fov: self.fisheye_fov,
+                mapping: match self.fisheye_mapping {
+                    FisheyeMappingArg::Equidistant => FisheyeMapping::Equidistant,
+                    FisheyeMappingArg::Equisolid => FisheyeMapping::Equisolid,
+                },
+            }),
+            Some(ProjectionArg::Equirectangular) => builder
+                .with_projection(Projection::Equirectangular)
+                .with_aspect_ratio(2.0),
+        };
+
+        // focus last, so that probing for it sees the camera as it will render.
+        if let Some(point) = self.focus_point {
+            builder = builder.with_focus_point(point)?;
+        }
+        if let Some((i, j)) = self.focus_pixel {
+            builder = builder.with_focus_on_pixel(world, i, j)?;
+        }
+        Ok(builder)
+    }
 }
 
 #[derive(ValueEnum, Debug, Clone, Copy)]
 enum Scene {
     Spheres,
     BookCover,
+    Dispersion,
+    Iridescence,
+    Translucent,
+    Lights,
+    Daylight,
+    /// The spheres, with the middle one bouncing while the camera circles them, over frames 0 to
+    /// 48.
+    Bouncing,
 }
 
-fn spheres<W: Write>(output: &mut W) -> Result<()> {
+fn spheres(args: &Args, run: SceneRun) -> Result<()> {
     // Materials
 
     let material_ground = Metal::new(Color::new(0.9, 0.9, 1.0), 0.05);
@@ -64,7 +812,7 @@ fn spheres<W: Write>(output: &mut W) -> Result<()> {
 
     // Camera
 
-    let camera = camera::CameraBuilder::default()
+    let camera = CameraBuilder::default()
         .with_image_width(1920)
         .with_aspect_ratio(16.0 / 9.0)
         .with_samples_per_pixel(500)
@@ -73,55 +821,124 @@ fn spheres<W: Write>(output: &mut W) -> Result<()> {
         .look_from(Point3::new(-2.0, 2.0, 1.0))
         .look_at(Point3::new(0.0, 0.0, -1.0))
         .with_up(Vec3::new(0.0, 1.0, 0.0))
-        .build();
+        .with_focus_point(Point3::new(0.0, 0.0, -1.0))?;
+    let camera = args.configure_camera(camera, &world)?.build()?;
 
     // Render
 
-    camera.render_to_io(&world, output)?;
-    drop(world);
+    run(&camera, &world, &LightList::default())
+}
 
-    Ok(())
+fn bouncing(args: &Args, run: SceneRun) -> Result<()> {
+    let frame = args.frame as f32;
+
+    // Materials
+
+    let material_ground = Metal::new(Color::new(0.9, 0.9, 1.0), 0.05);
+    let material_center = Lambertian::new(Color::new(0.1, 0.2, 0.5));
+    let material_left = Dielectric::new(1.5);
+    let material_right = Metal::new(Color::new(0.8, 0.6, 0.2), 0.0);
+
+    // Animation
+
+    // the ball leaves the ground quickly and slows to a stop at the top of each bounce.
+    let kick = Vec3::new(0.0, 0.3, 0.0);
+    let ground = |frame| Keyframe::new(frame, Vec3::default()).with_handles(kick, kick);
+    let top = Vec3::new(0.0, 0.6, 0.0);
+    let bounce = TransformAnimation::default().with_translation(
+        Track::new(Interpolation::Bezier)
+            .with_keyframe(ground(0.0))
+            .with_key(12.0, top)
+            .with_keyframe(ground(24.0))
+            .with_key(36.0, top)
+            .with_keyframe(ground(48.0)),
+    );
+
+    let orbit = (0..=4).fold(Track::new(Interpolation::CatmullRom), |track, k| {
+        let angle = (k as f32 * 30.0 - 120.0).to_radians();
+        track.with_key(
+            k as f32 * 12.0,
+            Point3::new(3.2 * angle.sin(), 2.0, 3.2 * angle.cos() - 1.0),
+        )
+    });
+    let camera_animation = CameraAnimation::default().with_look_from(orbit).with_vfov(
+        Track::new(Interpolation::Linear)
+            .with_key(0.0, 40.0)
+            .with_key(48.0, 30.0),
+    );
+
+    // World
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &material_ground);
+    let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &material_center);
+    let left_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), 0.5, &material_left);
+    let left_inner_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), -0.4, &material_left);
+    let right_sphere = Sphere::new(Point3::new(1.1, 0.0, -1.0), 0.5, &material_right);
+
+    let bounced = bounce.at(frame);
+    let center_sphere = Transformed::new(&center_sphere, bounced);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&center_sphere);
+    world.add(&left_sphere);
+    world.add(&left_inner_sphere);
+    world.add(&right_sphere);
+
+    // Camera
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1920)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(500)
+        .with_recursion_depth(50)
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0));
+    // focus once the camera is in place, so that the point is in front of it.
+    let camera = camera_animation
+        .apply(camera, frame)
+        .with_focus_point(Point3::new(0.0, 0.0, -1.0) + bounced.translation)?;
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &LightList::default())
 }
 
-fn book_cover<W: Write>(output: &mut W) -> Result<()> {
+fn book_cover(args: &Args, run: SceneRun) -> Result<()> {
     let mut world = HittableList::default();
 
     let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
     let mut spheres = Vec::with_capacity(22 * 22 + 3);
 
-    let mut sphere_materials: Vec<Box<dyn Material>> = (-11..11)
-        .flat_map(|i| (-11..11).map(move |j| (i, j)))
-        .map(|(i, j)| {
-            let choose_mat: f32 = rand::random();
-            let center = Point3::new(
-                i as f32 + 0.9 * rand::random::<f32>(),
-                0.2,
-                j as f32 + 0.9 * rand::random::<f32>(),
-            );
+    // the layout comes from the seed, so that checkpoints of the scene can be resumed.
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut sphere_materials: Vec<Box<dyn Material>> = Vec::with_capacity(22 * 22 + 3);
+    for (i, j) in (-11..11).flat_map(|i| (-11..11).map(move |j| (i, j))) {
+        let choose_mat: f32 = rng.gen();
+        let center = Point3::new(
+            i as f32 + 0.9 * rng.gen::<f32>(),
+            0.2,
+            j as f32 + 0.9 * rng.gen::<f32>(),
+        );
+        if (center - Point3::new(4.0, 0.2, 0.0)).len() <= 0.9 {
+            continue;
+        }
 
-            (choose_mat, center)
-        })
-        .filter(|(_, center)| (*center - Point3::new(4.0, 0.2, 0.0)).len() > 0.9)
-        .map(|(choose_mat, center)| {
-            spheres.push((center, 0.2));
-            if choose_mat < 0.8 {
-                // diffuse
-                let albedo = Color::random() * Color::random();
-                let material = Lambertian::new(albedo);
-                Box::new(material) as Box<dyn Material>
-            } else if choose_mat < 0.95 {
-                // metal
-                let albedo = Color::random_in_range(0.5, 1.0);
-                let fuzz = thread_rng().gen_range(0.0..0.5);
-                let material = Metal::new(albedo, fuzz);
-                Box::new(material) as Box<dyn Material>
-            } else {
-                // glass
-                let material = Dielectric::new(1.5);
-                Box::new(material) as Box<dyn Material>
-            }
-        })
-        .collect();
+        spheres.push((center, 0.2));
+        let material: Box<dyn Material> = if choose_mat < 0.8 {
+            // diffuse
+            let albedo = Color::random(&mut rng) * Color::random(&mut rng);
+            Box::new(Lambertian::new(albedo))
+        } else if choose_mat < 0.95 {
+            // metal
+            let albedo = Color::random_in_range(&mut rng, 0.5, 1.0);
+            let fuzz = rng.gen_range(0.0..0.5);
+            Box::new(Metal::new(albedo, fuzz))
+        } else {
+            // glass
+            Box::new(Dielectric::new(1.5))
+        };
+        sphere_materials.push(material);
+    }
 
     sphere_materials.push(Box::new(Dielectric::new(1.5)));
     sphere_materials.push(Box::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))));
@@ -158,26 +975,424 @@ fn book_cover<W: Write>(output: &mut W) -> Result<()> {
         .look_at(Point3::new(0.0, 0.0, 0.0))
         .with_up(Vec3::new(0.0, 1.0, 0.0))
         .with_defocus_angle(0.6)
-        .with_focus_dist(10.0)
-        .build();
+        .with_focus_dist(10.0);
+    let camera = args.configure_camera(camera, &world)?.build()?;
 
-    camera.render_to_io(&world, output)
+    run(&camera, &world, &LightList::default())
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn dispersion(args: &Args, run: SceneRun) -> Result<()> {
+    let material_ground = Lambertian::new(Color::new(0.8, 0.8, 0.8));
+    let material_crown = Dielectric::with_ior(Ior::BK7);
+    let material_flint = Dielectric::with_ior(Ior::SF11);
+    let material_cauchy = Dielectric::with_ior(Ior::Cauchy { a: 1.6, b: 0.05 });
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &material_ground);
+    let left_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), 0.5, &material_crown);
+    let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &material_flint);
+    let right_sphere = Sphere::new(Point3::new(1.1, 0.0, -1.0), 0.5, &material_cauchy);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&left_sphere);
+    world.add(&center_sphere);
+    world.add(&right_sphere);
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1280)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(500)
+        .with_recursion_depth(50)
+        .with_vertical_field_of_view(40.0)
+        .look_from(Point3::new(0.0, 1.0, 2.0))
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0));
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &LightList::default())
+}
 
-    let output = args.output.unwrap_or_else(|| PathBuf::from("/dev/stdout"));
+fn iridescence(args: &Args, run: SceneRun) -> Result<()> {
+    // a soap bubble: a film of soapy water around air, thinning towards the top as it drains.
+    let material_bubble = ThinFilm::new(
+        Dielectric::new(1.0),
+        1.33,
+        ThicknessGradient::new(
+            Point3::new(0.0, -0.5, -1.0),
+            Point3::new(0.0, 0.5, -1.0),
+            900.0,
+            250.0,
+        ),
+    );
+    // oil on a puddle-dark, polished metal.
+    let material_ground = ThinFilm::new(Metal::new(Color::new(0.3, 0.3, 0.3), 0.02), 1.47, 350.0);
+    let material_left = ThinFilm::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0), 1.5, 500.0);
+    let material_right = Lambertian::new(Color::new(0.1, 0.2, 0.5));
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &material_ground);
+    let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &material_bubble);
+    let left_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), 0.5, &material_left);
+    let right_sphere = Sphere::new(Point3::new(1.1, 0.0, -1.5), 0.5, &material_right);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&center_sphere);
+    world.add(&left_sphere);
+    world.add(&right_sphere);
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1280)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(500)
+        .with_recursion_depth(50)
+        .with_vertical_field_of_view(40.0)
+        .look_from(Point3::new(0.0, 0.5, 2.0))
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0));
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &LightList::default())
+}
+
+fn translucent(args: &Args, run: SceneRun) -> Result<()> {
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    // red light travels furthest through skin, giving it a warm glow where it's thin.
+    let material_skin = Subsurface::new(
+        1.4,
+        Color::new(0.12, 0.05, 0.03),
+        Color::new(0.95, 0.8, 0.7),
+    );
+    let material_marble =
+        Subsurface::new(1.5, Color::new(0.2, 0.2, 0.2), Color::new(0.99, 0.99, 0.98));
+    let material_wax =
+        Subsurface::new(1.45, Color::new(0.3, 0.25, 0.1), Color::new(0.98, 0.9, 0.6));
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &material_ground);
+    let left_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), 0.5, &material_skin);
+    let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &material_marble);
+    let right_sphere = Sphere::new(Point3::new(1.1, 0.0, -1.0), 0.5, &material_wax);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&left_sphere);
+    world.add(&center_sphere);
+    world.add(&right_sphere);
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1280)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(500)
+        .with_recursion_depth(200)
+        .with_vertical_field_of_view(40.0)
+        .look_from(Point3::new(0.0, 1.0, 2.0))
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0));
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &LightList::default())
+}
+
+fn lights(args: &Args, run: SceneRun) -> Result<()> {
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    let material_center = Lambertian::new(Color::new(0.7, 0.7, 0.7));
+    let material_left = Dielectric::new(1.5);
+    let material_right = Metal::new(Color::new(0.8, 0.6, 0.2), 0.1);
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, &material_ground);
+    let center_sphere = Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, &material_center);
+    let left_sphere = Sphere::new(Point3::new(-1.1, 0.0, -1.0), 0.5, &material_left);
+    let right_sphere = Sphere::new(Point3::new(1.1, 0.0, -1.0), 0.5, &material_right);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&center_sphere);
+    world.add(&left_sphere);
+    world.add(&right_sphere);
+
+    let key = SpotLight::new(
+        Point3::new(-2.0, 3.0, 1.0),
+        Point3::new(0.0, 0.0, -1.0),
+        Color::new(20.0, 18.0, 15.0),
+        25.0,
+        40.0,
+    );
+    let fill = PointLight::new(Point3::new(2.0, 1.0, 0.5), Color::new(1.0, 1.5, 2.0))
+        .with_falloff(Falloff::Linear);
+    let sun = DirectionalLight::new(Vec3::new(1.0, -1.0, -0.5), Color::new(0.8, 0.7, 0.6))
+        .with_angular_diameter(2.0);
+
+    let mut lights = LightList::default();
+    lights.add(&key);
+    lights.add(&fill);
+    lights.add(&sun);
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1280)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(100)
+        .with_recursion_depth(50)
+        .with_vertical_field_of_view(40.0)
+        .look_from(Point3::new(0.0, 1.0, 2.0))
+        .look_at(Point3::new(0.0, 0.0, -1.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0));
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &lights)
+}
+
+fn daylight(args: &Args, run: SceneRun) -> Result<()> {
+    let material_ground = Lambertian::new(Color::new(0.4, 0.4, 0.35));
+    let material_center = Lambertian::new(Color::new(0.8, 0.8, 0.8));
+    let material_left = Dielectric::new(1.5);
+    let material_right = Metal::new(Color::new(0.9, 0.9, 0.9), 0.0);
+
+    let ground_sphere = Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, &material_ground);
+    let center_sphere = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, &material_center);
+    let left_sphere = Sphere::new(Point3::new(-2.2, 1.0, 0.0), 1.0, &material_left);
+    let right_sphere = Sphere::new(Point3::new(2.2, 1.0, 0.0), 1.0, &material_right);
+
+    let mut world = HittableList::default();
+    world.add(&ground_sphere);
+    world.add(&center_sphere);
+    world.add(&left_sphere);
+    world.add(&right_sphere);
+
+    let sky = PreethamSky::new(args.sun_elevation, args.sun_azimuth, args.turbidity);
+    let sun = sky.sun();
+
+    let mut lights = LightList::default();
+    lights.add(&sun);
+
+    let camera = CameraBuilder::default()
+        .with_image_width(1280)
+        .with_aspect_ratio(16.0 / 9.0)
+        .with_samples_per_pixel(100)
+        .with_recursion_depth(50)
+        .with_vertical_field_of_view(50.0)
+        .look_from(Point3::new(0.0, 2.0, 8.0))
+        .look_at(Point3::new(0.0, 1.5, 0.0))
+        .with_up(Vec3::new(0.0, 1.0, 0.0))
+        .with_background(Background::Sky(sky));
+    let camera = args.configure_camera(camera, &world)?.build()?;
+
+    run(&camera, &world, &lights)
+}
+
+/// Renders a scene, writing the result wherever the command line asked for.
+fn render(args: &Args, camera: &Camera, world: &HittableList, lights: &LightList) -> Result<()> {
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/dev/stdout"));
+
+    if let Some(StereoArg::Separate) = args.stereo {
+        for (eye, suffix) in [(Eye::Left, "left"), (Eye::Right, "right")] {
+            write_output(args, &util::suffixed_path(&output, suffix), |mut writer| {
+                camera.render_eye_to_io(world, lights, eye, &mut writer)
+            })?;
+        }
+        return Ok(());
+    }
+
+    if let Some(address) = &args.coordinate {
+        // workers set up the scene from the same command line.
+        let job = std::env::args().collect::<Vec<_>>().join("\0");
+        let coordinator = Coordinator::bind(address.as_str(), job.into_bytes())?
+            .with_timeout(args.worker_timeout);
+        eprintln!("Waiting for workers on {}", coordinator.local_addr()?);
+        return write_output(args, &output, |mut writer| {
+            camera.render_distributed_to_io(world, lights, &coordinator, &mut writer)
+        });
+    }
+
+    write_output(args, &output, |mut writer| {
+        camera.render_to_io(world, lights, &mut writer)
+    })
+}
+
+/// Writes the image `render` produces to `path`.  Progressive renders replace snapshots at `path`
+/// as they go, so the final image replaces the last of them the same way, and renders writing
+/// tiles have already written the image there tile by tile.
+fn write_output(
+    args: &Args,
+    path: &Path,
+    render: impl FnOnce(&mut dyn std::io::Write) -> Result<()>,
+) -> Result<()> {
+    if args.write_tiles {
+        return render(&mut std::io::sink());
+    }
+    if args.progressive {
+        let mut contents = Vec::new();
+        render(&mut contents)?;
+        return util::write_atomically(path, &contents);
+    }
+
+    let mut writer = create_output(path)?;
+    render(&mut writer)
+}
+
+fn create_output(path: &Path) -> Result<BufWriter<File>> {
     let file = std::fs::OpenOptions::new()
         .write(true)
         .read(false)
         .truncate(true)
         .create(true)
-        .open(output.as_path())?;
-    let mut writer = BufWriter::new(file);
+        .open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+/// What to do with a scene once it's set up: the camera to render it with, the world, and the
+/// lights to sample.
+type SceneRun<'a> = Box<dyn FnOnce(&Camera, &HittableList, &LightList) -> Result<()> + 'a>;
+
+/// Sets up the scene the command line asks for as it is at the frame of its animation being
+/// rendered, and hands it to `run`.  Scenes without animation look the same at every frame.
+fn scene(args: &Args, run: SceneRun) -> Result<()> {
+    let Some(scene) = args.scene else {
+        return Err(Error::new(ErrorKind::InvalidInput, "no scene given"));
+    };
+
+    match scene {
+        Scene::Spheres => spheres(args, run),
+        Scene::BookCover => book_cover(args, run),
+        Scene::Dispersion => dispersion(args, run),
+        Scene::Iridescence => iridescence(args, run),
+        Scene::Translucent => translucent(args, run),
+        Scene::Lights => lights(args, run),
+        Scene::Daylight => daylight(args, run),
+        Scene::Bouncing => bouncing(args, run),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if let Some(address) = &args.worker {
+        let worker = Worker::connect(address.as_str())?;
+        let job = String::from_utf8_lossy(worker.job()).into_owned();
+        let args = Args::try_parse_from(job.split('\0'))
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        return scene(
+            &args,
+            Box::new(|camera, world, lights| camera.work(world, lights, worker)),
+        );
+    }
+
+    if matches!(args.stereo, Some(StereoArg::Separate)) && args.output.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "rendering eyes to separate files requires an output path",
+        ));
+    }
+    if args.write_tiles
+        && (args.output.is_none() || matches!(args.stereo, Some(StereoArg::Separate)))
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "writing tiles requires an output path, and eyes rendered into a single image",
+        ));
+    }
+    if (args.frames.is_some() || args.turntable.is_some())
+        && !args.contact_sheet
+        && args.output.is_none()
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "rendering frames requires an output path to number them after",
+        ));
+    }
+    if !args.aovs.is_empty() && args.aov_output.is_none() && args.output.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "writing AOVs requires an output path, or one of their own",
+        ));
+    }
+    if args.progressive && args.output.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "rendering progressively requires an output path",
+        ));
+    }
+
+    if args.coordinate.is_some() && matches!(args.stereo, Some(StereoArg::Separate)) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "distributed renders write both eyes into a single image",
+        ));
+    }
+
+    let frames = match args.turntable {
+        Some(frames) => Some(0..=frames - 1),
+        None => args.frames.clone(),
+    };
+    let Some(frames) = frames else {
+        return scene(
+            &args,
+            Box::new(|camera, world, lights| render(&args, camera, world, lights)),
+        );
+    };
+    if args.contact_sheet {
+        return contact_sheet(&args, frames);
+    }
+
+    for frame in frames {
+        // each frame writes its own files, numbered after it.
+        let number = format!("{frame:04}");
+        let suffixed =
+            |path: &Option<PathBuf>| path.as_ref().map(|path| util::suffixed_path(path, &number));
+        let args = Args {
+            output: suffixed(&args.output),
+            sample_heatmap: suffixed(&args.sample_heatmap),
+            checkpoint: suffixed(&args.checkpoint),
+            aov_output: suffixed(&args.aov_output),
+            frame,
+            ..args.clone()
+        };
+
+        eprintln!("Frame {frame}");
+        scene(
+            &args,
+            Box::new(|camera, world, lights| render(&args, camera, world, lights)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Renders `frames` and writes them laid out in a grid to the output.
+fn contact_sheet(args: &Args, frames: RangeInclusive<u32>) -> Result<()> {
+    let count = u64::from(frames.end() - frames.start()) + 1;
+    let mut sheet: Option<ContactSheet> = None;
+
+    for (index, frame) in frames.enumerate() {
+        let args = Args {
+            checkpoint: args
+                .checkpoint
+                .as_ref()
+                .map(|path| util::suffixed_path(path, &format!("{frame:04}"))),
+            frame,
+            ..args.clone()
+        };
+
+        eprintln!("Frame {frame}");
+        scene(
+            &args,
+            Box::new(|camera, world, lights| {
+                let (width, height, colors) = camera.render_colors(world, lights)?;
+                sheet
+                    .get_or_insert_with(|| ContactSheet::new(count, width, height))
+                    .add_frame(index as u64, &colors);
+                Ok(())
+            }),
+        )?;
+    }
 
-    match args.scene {
-        Scene::Spheres => spheres(&mut writer),
-        Scene::BookCover => book_cover(&mut writer),
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/dev/stdout"));
+    let mut writer = create_output(&output)?;
+    if let Some(sheet) = sheet {
+        sheet.write_ppm(&mut writer)?;
     }
+    writer.flush()
 }
@@ -1,19 +1,129 @@
-use crate::{geometry::HitRecord, ray::Ray, vec3::Color};
+use crate::{
+    geometry::HitRecord,
+    ray::Ray,
+    sampler::Sampler,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    vec3::Color,
+};
 
 use super::Material;
 
+/// Wavelength (in nanometers) of the Fraunhofer d line, where glass catalogs quote their
+/// refractive index.  Used when rendering without wavelength information.
+pub(super) const D_LINE: f32 = 587.56;
+
+/// The index of refraction of a material, possibly varying with wavelength.
+#[derive(Debug, Copy, Clone)]
+pub enum Ior {
+    /// The same index of refraction at every wavelength.
+    Constant(f32),
+    /// Cauchy's equation, `n = a + b / λ²`, with `λ` in micrometers.
+    Cauchy { a: f32, b: f32 },
+    /// The Sellmeier equation, `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)`, with `λ` in micrometers.
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Ior {
+    /// Schott N-BK7, a common borosilicate crown glass.
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.039_612, 0.231_792_3, 1.010_469_4],
+        c: [0.006_000_7, 0.020_017_9, 103.560_65],
+    };
+
+    /// Schott N-SF11, a dense flint glass with strong dispersion.
+    pub const SF11: Self = Self::Sellmeier {
+        b: [1.737_597, 0.313_747_3, 1.898_781],
+        c: [0.013_188_7, 0.062_306_8, 155.236_3],
+    };
+
+    /// Evaluates the index of refraction at `lambda`, in nanometers.
+    pub fn at(&self, lambda: f32) -> f32 {
+        let lambda_um = lambda * 1e-3;
+        let lambda_sq = lambda_um * lambda_um;
+        match *self {
+            Ior::Constant(ior) => ior,
+            Ior::Cauchy { a, b } => a + b / lambda_sq,
+            Ior::Sellmeier { b, c } => {
+                let sum: f32 = b
+                    .iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * lambda_sq / (lambda_sq - c))
+                    .sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    /// Whether the index of refraction varies with wavelength.
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+}
+
 #[derive(Debug)]
 pub struct Dielectric {
-    ior: f32,
+    ior: Ior,
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
+        let scattered = self.scatter_with_ior(ray, hit_record, self.ior.at(D_LINE), sampler);
+
+        Some((scattered, attenuation))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        // each wavelength refracts in a different direction, so only the hero wavelength can
+        // follow the scattered ray.
+        if self.ior.is_dispersive() {
+            lambda.terminate_secondary();
+        }
+        let scattered = self.scatter_with_ior(ray, hit_record, self.ior.at(lambda.hero()), sampler);
+
+        Some((scattered, SampledSpectrum::splat(1.0)))
+    }
+}
+
+impl Dielectric {
+    pub fn new(ior: f32) -> Self {
+        Self {
+            ior: Ior::Constant(ior),
+        }
+    }
+
+    /// Creates a dielectric whose index of refraction may vary with wavelength.  Dispersion is
+    /// only visible when rendering spectrally.
+    pub fn with_ior(ior: Ior) -> Self {
+        Self { ior }
+    }
+
+    pub fn ior(&self) -> Ior {
+        self.ior
+    }
+
+    fn scatter_with_ior(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        ior: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Ray {
         let refraction_ratio = if hit_record.front_face {
-            self.ior.recip()
+            ior.recip()
         } else {
-            self.ior
+            ior
         };
 
         let unit_direction = ray.direction().normalize();
@@ -21,22 +131,14 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let use_schlick = reflectance(cos_theta, refraction_ratio) > rand::random();
+        let use_schlick = reflectance(cos_theta, refraction_ratio) > sampler.get_1d();
         let direction = if cannot_refract || use_schlick {
             unit_direction.reflect(hit_record.normal)
         } else {
             unit_direction.refract(hit_record.normal, refraction_ratio)
         };
 
-        let scattered = Ray::new(hit_record.point, direction);
-
-        Some((scattered, attenuation))
-    }
-}
-
-impl Dielectric {
-    pub fn new(ior: f32) -> Self {
-        Self { ior }
+        Ray::new(hit_record.point, direction)
     }
 }
 
@@ -1,5 +1,9 @@
+use std::f32::consts::FRAC_1_PI;
+
 use crate::{
+    geometry::HitRecord,
     ray::Ray,
+    sampler::Sampler,
     vec3::{Color, Vec3},
 };
 
@@ -18,8 +22,13 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_record: &crate::geometry::HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit_record.normal + Vec3::random_on_unit_sphere();
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        let mut scatter_direction = hit_record.normal + Vec3::sample_unit_sphere(sampler.get_2d());
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
@@ -27,4 +36,13 @@ impl Material for Lambertian {
         let attenuation = self.albedo;
         Some((scattered, attenuation))
     }
+
+    fn eval(&self, _ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let cos_theta = hit_record.normal.dot(direction);
+        (cos_theta > 0.0).then(|| self.albedo * (cos_theta * FRAC_1_PI))
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
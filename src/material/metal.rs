@@ -1,5 +1,9 @@
+use std::f32::consts::PI;
+
 use crate::{
+    geometry::HitRecord,
     ray::Ray,
+    sampler::Sampler,
     vec3::{Color, Vec3},
 };
 
@@ -15,16 +19,70 @@ impl Metal {
     pub fn new(albedo: Color, fuzz: f32) -> Self {
         Self { albedo, fuzz }
     }
+
+    pub fn albedo(&self) -> Color {
+        self.albedo
+    }
+
+    pub fn fuzz(&self) -> f32 {
+        self.fuzz
+    }
+
+    /// The density, over solid angle, of the directions rays along `ray` are scattered in,
+    /// towards `direction` (a unit vector).  `None` for a perfect mirror, which only ever
+    /// scatters them one way, and for directions it never scatters them in.
+    pub(super) fn scatter_density(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        direction: &Vec3,
+    ) -> Option<f32> {
+        if self.fuzz <= 0.0 || hit_record.normal.dot(direction) <= 0.0 {
+            return None;
+        }
+
+        // scattered rays head for points spread evenly over a sphere of radius `fuzz` around the
+        // tip of the reflected direction, so the density is that of the points on the sphere
+        // along `direction`, at distance t, seen at an angle whose cosine is sqrt(d) / fuzz.
+        let reflected = ray.direction().normalize().reflect(hit_record.normal);
+        let cos_alpha = direction.dot(&reflected);
+        let d = cos_alpha * cos_alpha - 1.0 + self.fuzz * self.fuzz;
+        if d <= 0.0 {
+            return None;
+        }
+        let density = [cos_alpha - d.sqrt(), cos_alpha + d.sqrt()]
+            .into_iter()
+            .filter(|&t| t > 0.0)
+            .map(|t| t * t / (4.0 * PI * self.fuzz * d.sqrt()))
+            .sum::<f32>();
+        (density > 0.0).then_some(density)
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record: &crate::geometry::HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
         let reflected = ray.direction().normalize().reflect(hit_record.normal);
         let scattered = Ray::new(
             hit_record.point,
-            reflected + self.fuzz * Vec3::random_on_unit_sphere(),
+            reflected + self.fuzz * Vec3::sample_unit_sphere(sampler.get_2d()),
         );
         let attenuation = self.albedo;
         Some((scattered, attenuation))
     }
+
+    /// Scattered rays are weighted by the albedo alone, so the light reflected towards `ray` is
+    /// the albedo times the density of the rays scattered towards `direction`.
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        self.scatter_density(ray, hit_record, direction)
+            .map(|density| density * self.albedo)
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
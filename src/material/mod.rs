@@ -1,13 +1,65 @@
-use crate::{geometry::HitRecord, ray::Ray, vec3::Color};
+use crate::{
+    geometry::HitRecord,
+    ray::Ray,
+    sampler::Sampler,
+    spectrum::{Radiance, SampledSpectrum, SampledWavelengths},
+    vec3::{Color, Vec3},
+};
 
 mod dielectric;
 mod lambertian;
 mod metal;
+mod subsurface;
+mod thin_film;
 
 pub use dielectric::*;
 pub use lambertian::*;
 pub use metal::*;
+pub use subsurface::*;
+pub use thin_film::*;
 
 pub trait Material: std::fmt::Debug + Send + Sync {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)>;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)>;
+
+    /// Scatters a ray carrying the wavelengths in `lambda`.  By default this upsamples the
+    /// attenuation from [`Material::scatter`] to a spectrum; materials whose behavior depends on
+    /// wavelength override it.
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.scatter(ray, hit_record, sampler)
+            .map(|(scattered, attenuation)| {
+                (scattered, SampledSpectrum::from_rgb(attenuation, lambda))
+            })
+    }
+
+    /// Evaluates the fraction of light arriving from `direction` (a unit vector) that the surface
+    /// scatters back along `ray`, including the cosine term.  Used when sampling lights
+    /// explicitly; perfectly specular materials can never reflect light arriving from a single
+    /// direction towards the camera, so they return `None`, as does the default implementation.
+    fn eval(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Option<Color> {
+        None
+    }
+
+    /// The color of the surface as it would look lit evenly from every side, for the albedo AOV.
+    /// Surfaces that reflect or let through light without tinting it, as by default, are white.
+    fn albedo(&self) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    /// The medium filling the inside of a closed surface made of this material, if any.  Rays
+    /// travelling through the inside of the surface (i.e. hitting it from the back) may scatter
+    /// within the medium before they reach it.
+    fn interior(&self) -> Option<&Medium> {
+        None
+    }
 }
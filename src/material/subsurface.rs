@@ -0,0 +1,141 @@
+use crate::{
+    geometry::HitRecord,
+    ray::Ray,
+    sampler::Sampler,
+    spectrum::{Radiance, SampledSpectrum, SampledWavelengths},
+    vec3::Color,
+};
+
+use super::{Dielectric, Material};
+
+/// The shortest mean free path a medium can have, which keeps its density finite.
+const MIN_MEAN_FREE_PATH: f32 = 1e-4;
+
+/// A homogeneous, isotropically scattering medium.
+#[derive(Debug, Copy, Clone)]
+pub struct Medium {
+    sigma_t: Color,
+    albedo: Color,
+}
+
+/// The outcome of a ray travelling through a [`Medium`].
+pub enum MediumEvent<S> {
+    /// The ray scattered at `ray.at(t)`.
+    Scattered { t: f32, weight: S },
+    /// The ray made it through the medium to the surface it was headed for.
+    Passed { weight: S },
+}
+
+impl Medium {
+    /// Creates a medium where light travels `mean_free_path` units on average before interacting
+    /// with a particle, and scatters with probability `albedo` when it does (rather than being
+    /// absorbed).  Both are given per color channel.  Mean free paths shorter than 1e-4 units,
+    /// including negative ones, are taken as 1e-4.
+    pub fn new(mean_free_path: Color, albedo: Color) -> Self {
+        let sigma_t = Color::new(
+            mean_free_path.x().max(MIN_MEAN_FREE_PATH).recip(),
+            mean_free_path.y().max(MIN_MEAN_FREE_PATH).recip(),
+            mean_free_path.z().max(MIN_MEAN_FREE_PATH).recip(),
+        );
+        Self { sigma_t, albedo }
+    }
+
+    /// Samples where `ray` interacts with the medium, if it does so before travelling `t_max`
+    /// units along its direction.
+    ///
+    /// A channel is chosen at random to sample the distance with, and the result is weighted by
+    /// the average probability over all channels, so that media whose mean free path varies
+    /// strongly between channels stay well behaved.
+    pub fn sample<S: Radiance>(
+        &self,
+        ray: &Ray,
+        t_max: f32,
+        lambda: &S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> MediumEvent<S> {
+        let speed = ray.direction().len();
+        let sigma_t = S::from_rgb(self.sigma_t, lambda);
+
+        let channel = ((sampler.get_1d() * S::CHANNELS as f32) as usize).min(S::CHANNELS - 1);
+        let distance = -(1.0 - sampler.get_1d()).ln() / sigma_t.channel(channel);
+
+        let t_max_distance = t_max * speed;
+        if distance < t_max_distance {
+            let transmittance = sigma_t.map(|s| (-s * distance).exp()) * sigma_t;
+            let pdf = transmittance.average();
+            if pdf <= 0.0 {
+                return MediumEvent::Passed {
+                    weight: S::default(),
+                };
+            }
+            let albedo = S::from_rgb(self.albedo, lambda);
+            MediumEvent::Scattered {
+                t: distance / speed,
+                weight: albedo * transmittance / pdf,
+            }
+        } else {
+            let transmittance = sigma_t.map(|s| (-s * t_max_distance).exp());
+            let pdf = transmittance.average();
+            if pdf <= 0.0 {
+                return MediumEvent::Passed {
+                    weight: S::default(),
+                };
+            }
+            MediumEvent::Passed {
+                weight: transmittance / pdf,
+            }
+        }
+    }
+}
+
+/// A translucent material, such as skin, marble, wax or milk.  Light refracts into the surface,
+/// takes a random walk through the medium inside, and eventually leaves it somewhere else.
+///
+/// Only closed surfaces should use this material, since a ray is considered to be inside the
+/// medium whenever it's heading towards the back face of the surface.
+#[derive(Debug)]
+pub struct Subsurface {
+    surface: Dielectric,
+    medium: Medium,
+}
+
+impl Subsurface {
+    /// Creates a translucent material with a smooth surface of index `ior`, filled with a medium
+    /// with the given per-channel mean free path and albedo.
+    pub fn new(ior: f32, mean_free_path: Color, albedo: Color) -> Self {
+        Self {
+            surface: Dielectric::new(ior),
+            medium: Medium::new(mean_free_path, albedo),
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        self.surface.scatter(ray, hit_record, sampler)
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        self.surface
+            .scatter_spectral(ray, hit_record, lambda, sampler)
+    }
+
+    fn albedo(&self) -> Color {
+        self.medium.albedo
+    }
+
+    fn interior(&self) -> Option<&Medium> {
+        Some(&self.medium)
+    }
+}
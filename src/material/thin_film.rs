@@ -0,0 +1,296 @@
+use std::f32::consts::TAU;
+
+use crate::{
+    geometry::HitRecord,
+    ray::Ray,
+    sampler::Sampler,
+    spectrum::{rgb_to_spectrum, Radiance, SampledSpectrum, SampledWavelengths},
+    vec3::{Color, Point3, Vec3},
+};
+
+use super::{Dielectric, Material, Metal, D_LINE};
+
+/// The thickness of a thin film (in nanometers) across a surface.
+pub trait FilmThickness: std::fmt::Debug + Send + Sync {
+    fn thickness(&self, point: &Point3) -> f32;
+}
+
+/// A film of the same thickness everywhere.
+impl FilmThickness for f32 {
+    fn thickness(&self, _point: &Point3) -> f32 {
+        *self
+    }
+}
+
+/// A film whose thickness varies linearly between two points, such as a soap bubble that has
+/// drained towards its bottom.
+#[derive(Debug)]
+pub struct ThicknessGradient {
+    from: Point3,
+    axis: Vec3,
+    start: f32,
+    end: f32,
+}
+
+impl ThicknessGradient {
+    /// Creates a film that is `start` nanometers thick at `from` and `end` nanometers thick at
+    /// `to`.  Past either end, the thickness stays constant.
+    pub fn new(from: Point3, to: Point3, start: f32, end: f32) -> Self {
+        let axis = to - from;
+        Self {
+            from,
+            axis: axis / axis.len_squared(),
+            start,
+            end,
+        }
+    }
+}
+
+impl FilmThickness for ThicknessGradient {
+    fn thickness(&self, point: &Point3) -> f32 {
+        let t = (*point - self.from).dot(&self.axis).clamp(0.0, 1.0);
+        self.start + t * (self.end - self.start)
+    }
+}
+
+/// A thin transparent coating on top of another material, such as a soap film or an oil slick.
+/// Light reflecting off the top and bottom of the film interferes, so the reflectance of the
+/// surface varies with wavelength and viewing angle.
+#[derive(Debug)]
+pub struct ThinFilm<M, T = f32> {
+    base: M,
+    ior: f32,
+    thickness: T,
+}
+
+impl<M, T: FilmThickness> ThinFilm<M, T> {
+    /// Coats `base` with a film with the given index of refraction and thickness (in nanometers).
+    pub fn new(base: M, ior: f32, thickness: T) -> Self {
+        Self {
+            base,
+            ior,
+            thickness,
+        }
+    }
+}
+
+impl<T: FilmThickness> ThinFilm<Dielectric, T> {
+    fn scatter_with<S: Radiance>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &S::Wavelengths,
+        substrate_ior: f32,
+        sampler: &mut dyn Sampler,
+    ) -> (Ray, S) {
+        let (outside_ior, inside_ior) = if hit_record.front_face {
+            (1.0, substrate_ior)
+        } else {
+            (substrate_ior, 1.0)
+        };
+
+        let unit_direction = ray.direction().normalize();
+        let cos_theta = (-unit_direction.dot(&hit_record.normal)).min(1.0);
+        let thickness = self.thickness.thickness(&hit_record.point);
+
+        let reflectance = S::from_fn(lambda, |l| {
+            film_reflectance(
+                cos_theta,
+                l,
+                outside_ior,
+                self.ior,
+                thickness,
+                Substrate::Dielectric(inside_ior),
+            )
+        });
+
+        // choose between reflection and refraction in proportion to how much light each carries,
+        // then weight by the per-channel reflectance to keep the estimate unbiased.
+        let reflect_probability = reflectance.average();
+        if sampler.get_1d() < reflect_probability {
+            let direction = unit_direction.reflect(hit_record.normal);
+            (
+                Ray::new(hit_record.point, direction),
+                reflectance / reflect_probability,
+            )
+        } else {
+            let direction = unit_direction.refract(hit_record.normal, outside_ior / inside_ior);
+            (
+                Ray::new(hit_record.point, direction),
+                reflectance.map(|r| 1.0 - r) / (1.0 - reflect_probability),
+            )
+        }
+    }
+}
+
+impl<T: FilmThickness> Material for ThinFilm<Dielectric, T> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        let substrate_ior = self.base.ior().at(D_LINE);
+        Some(self.scatter_with(ray, hit_record, &(), substrate_ior, sampler))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        let ior = self.base.ior();
+        if ior.is_dispersive() {
+            lambda.terminate_secondary();
+        }
+        Some(self.scatter_with(ray, hit_record, lambda, ior.at(lambda.hero()), sampler))
+    }
+}
+
+impl<T: FilmThickness> ThinFilm<Metal, T> {
+    /// How much of the light at each wavelength the coated metal reflects along `ray`.
+    fn reflectance<S: Radiance>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &S::Wavelengths,
+    ) -> S {
+        let unit_direction = ray.direction().normalize();
+        let cos_theta = (-unit_direction.dot(&hit_record.normal)).clamp(0.0, 1.0);
+        let thickness = self.thickness.thickness(&hit_record.point);
+        let albedo = self.base.albedo();
+
+        S::from_fn(lambda, |l| {
+            film_reflectance(
+                cos_theta,
+                l,
+                1.0,
+                self.ior,
+                thickness,
+                Substrate::Conductor(rgb_to_spectrum(albedo, l)),
+            )
+        })
+    }
+
+    fn scatter_with<S: Radiance>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &S::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> (Ray, S) {
+        let attenuation = self.reflectance(ray, hit_record, lambda);
+        let reflected = ray.direction().normalize().reflect(hit_record.normal);
+        let scattered = Ray::new(
+            hit_record.point,
+            reflected + self.base.fuzz() * Vec3::sample_unit_sphere(sampler.get_2d()),
+        );
+        (scattered, attenuation)
+    }
+}
+
+impl<T: FilmThickness> Material for ThinFilm<Metal, T> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        Some(self.scatter_with(ray, hit_record, &(), sampler))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, SampledSpectrum)> {
+        Some(self.scatter_with(ray, hit_record, lambda, sampler))
+    }
+
+    /// Like the bare metal's, with the film's reflectance in place of its albedo.
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let density = self.base.scatter_density(ray, hit_record, direction)?;
+        Some(density * self.reflectance::<Color>(ray, hit_record, &()))
+    }
+
+    fn albedo(&self) -> Color {
+        self.base.albedo()
+    }
+}
+
+/// What lies underneath a thin film.
+enum Substrate {
+    /// A transparent material with the given index of refraction.
+    Dielectric(f32),
+    /// A metal with the given reflectance at normal incidence.
+    Conductor(f32),
+}
+
+/// Computes the fraction of unpolarized light of wavelength `lambda` that a film reflects, when
+/// arriving from a medium with index `outside_ior` at an angle whose cosine is `cos_theta`.
+///
+/// This sums the infinite series of reflections inside the film (the Airy formula), averaging the
+/// s- and p-polarized reflectances.
+fn film_reflectance(
+    cos_theta: f32,
+    lambda: f32,
+    outside_ior: f32,
+    film_ior: f32,
+    thickness: f32,
+    substrate: Substrate,
+) -> f32 {
+    let sin2_theta = 1.0 - cos_theta * cos_theta;
+
+    let sin2_film = (outside_ior / film_ior).powi(2) * sin2_theta;
+    if sin2_film >= 1.0 {
+        return 1.0;
+    }
+    let cos_film = (1.0 - sin2_film).sqrt();
+
+    let (rs_bottom, rp_bottom) = match substrate {
+        Substrate::Dielectric(substrate_ior) => {
+            let sin2_substrate = (outside_ior / substrate_ior).powi(2) * sin2_theta;
+            if sin2_substrate >= 1.0 {
+                // no light makes it into the substrate, and the film doesn't absorb any.
+                return 1.0;
+            }
+            let cos_substrate = (1.0 - sin2_substrate).sqrt();
+            (
+                fresnel_s(film_ior, cos_film, substrate_ior, cos_substrate),
+                fresnel_p(film_ior, cos_film, substrate_ior, cos_substrate),
+            )
+        }
+        // metals reflect with a phase shift of roughly half a wavelength, like a very dense
+        // dielectric would.
+        Substrate::Conductor(reflectance) => {
+            let r = -reflectance.clamp(0.0, 1.0).sqrt();
+            (r, r)
+        }
+    };
+
+    let rs_top = fresnel_s(outside_ior, cos_theta, film_ior, cos_film);
+    let rp_top = fresnel_p(outside_ior, cos_theta, film_ior, cos_film);
+
+    let phase = TAU * 2.0 * film_ior * thickness * cos_film / lambda;
+    let cos_phase = phase.cos();
+    let airy = |top: f32, bottom: f32| {
+        let cross = 2.0 * top * bottom * cos_phase;
+        (top * top + bottom * bottom + cross) / (1.0 + top * top * bottom * bottom + cross)
+    };
+
+    0.5 * (airy(rs_top, rs_bottom) + airy(rp_top, rp_bottom))
+}
+
+/// Fresnel amplitude reflection coefficient for s-polarized light.
+fn fresnel_s(n_i: f32, cos_i: f32, n_t: f32, cos_t: f32) -> f32 {
+    (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t)
+}
+
+/// Fresnel amplitude reflection coefficient for p-polarized light.
+fn fresnel_p(n_i: f32, cos_i: f32, n_t: f32, cos_t: f32) -> f32 {
+    (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t)
+}
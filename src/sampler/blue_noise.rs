@@ -0,0 +1,193 @@
+use std::sync::OnceLock;
+
+use super::{
+    hash, mix_bits, shuffled_index,
+    sobol::{owen_scramble, sobol_sample, to_float},
+    SampleState, Sampler, ONE_MINUS_EPSILON,
+};
+
+/// Width and height of the blue noise mask, which tiles the image.
+const MASK_SIZE: usize = 64;
+
+/// Takes samples from the Sobol sequence, scrambled the same way for every pixel, then shifts
+/// each pixel's samples by the value of a blue noise mask at that pixel (toroidally, wrapping
+/// around `[0, 1)`).  Like [`super::SobolSampler`], each dimension shuffles the order of the
+/// samples, so that the dimensions don't correlate with each other.
+///
+/// Neighboring pixels see the same points shifted by very different amounts, so their errors
+/// differ, but the shifts vary smoothly over larger areas: what noise is left in the image is
+/// high frequency, which the eye picks up less, and which blurs away when the image is shrunk.
+#[derive(Debug, Default, Clone)]
+pub struct BlueNoiseSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: SampleState::new(seed),
+        }
+    }
+
+    /// The index of the Sobol point this sample takes for the next dimension and the scramble
+    /// for that dimension, both shared by every pixel, and the shift for this pixel, read from
+    /// the mask at an offset chosen for that dimension.
+    fn next_dimension(&mut self) -> (u32, u32, f32) {
+        let h = hash(&[self.state.seed, self.state.next_dimension()]);
+        let offset_x = (h >> 8) as usize % MASK_SIZE;
+        let offset_y = (h >> 16) as usize % MASK_SIZE;
+
+        let x = (self.state.pixel.0 as usize + offset_x) % MASK_SIZE;
+        let y = (self.state.pixel.1 as usize + offset_y) % MASK_SIZE;
+        let index = shuffled_index(self.state.index, self.samples_per_pixel, mix_bits(h) as u32);
+        (index, (h >> 32) as u32, mask()[y * MASK_SIZE + x])
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (index, scramble, shift) = self.next_dimension();
+        let value = to_float(owen_scramble(sobol_sample(index, 0), scramble));
+        shift_toroidally(value, shift)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        // both dimensions take the same point, which is what keeps them well distributed together.
+        let (index, scramble_x, shift_x) = self.next_dimension();
+        let (_, scramble_y, shift_y) = self.next_dimension();
+        (
+            shift_toroidally(
+                to_float(owen_scramble(sobol_sample(index, 0), scramble_x)),
+                shift_x,
+            ),
+            shift_toroidally(
+                to_float(owen_scramble(sobol_sample(index, 1), scramble_y)),
+                shift_y,
+            ),
+        )
+    }
+}
+
+fn shift_toroidally(value: f32, shift: f32) -> f32 {
+    (value + shift).fract().min(ONE_MINUS_EPSILON)
+}
+
+/// The blue noise mask: every value in `[0, 1)` appears once, spread so that similar values are
+/// far apart.
+fn mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+/// Generates a blue noise mask with Ulichney's void-and-cluster method ("The void-and-cluster
+/// method for dither array generation", 1993).
+///
+/// Points are ranked by repeatedly removing the point in the tightest cluster from an evenly
+/// spread pattern, then repeatedly adding a point in the largest void, where clusters and voids
+/// are found by blurring the pattern with a Gaussian that wraps around the edges.
+fn void_and_cluster() -> Vec<f32> {
+    const PIXELS: usize = MASK_SIZE * MASK_SIZE;
+    const SIGMA: f32 = 1.5;
+
+    let kernel: Vec<f32> = (0..PIXELS)
+        .map(|k| {
+            let (x, y) = (k % MASK_SIZE, k / MASK_SIZE);
+            let dx = x.min(MASK_SIZE - x) as f32;
+            let dy = y.min(MASK_SIZE - y) as f32;
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    #[derive(Clone)]
+    struct Pattern {
+        points: Vec<bool>,
+        energy: Vec<f32>,
+    }
+
+    impl Pattern {
+        fn toggle(&mut self, index: usize, kernel: &[f32]) {
+            self.points[index] = !self.points[index];
+            let sign = if self.points[index] { 1.0 } else { -1.0 };
+            let (px, py) = (index % MASK_SIZE, index / MASK_SIZE);
+            for (k, energy) in self.energy.iter_mut().enumerate() {
+                let dx = (k % MASK_SIZE + MASK_SIZE - px) % MASK_SIZE;
+                let dy = (k / MASK_SIZE + MASK_SIZE - py) % MASK_SIZE;
+                *energy += sign * kernel[dy * MASK_SIZE + dx];
+            }
+        }
+
+        fn tightest_cluster(&self) -> usize {
+            self.find(true, |a, b| a > b)
+        }
+
+        fn largest_void(&self) -> usize {
+            self.find(false, |a, b| a < b)
+        }
+
+        fn find(&self, point: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+            let mut best = None;
+            for (k, &energy) in self.energy.iter().enumerate() {
+                if self.points[k] == point && best.is_none_or(|(_, e)| better(energy, e)) {
+                    best = Some((k, energy));
+                }
+            }
+            best.map_or(0, |(k, _)| k)
+        }
+    }
+
+    let mut pattern = Pattern {
+        points: vec![false; PIXELS],
+        energy: vec![0.0; PIXELS],
+    };
+
+    // start from a deterministic random pattern covering a tenth of the mask.
+    let initial_points = PIXELS / 10;
+    let mut seed = 0;
+    let mut placed = 0;
+    while placed < initial_points {
+        let k = mix_bits(seed) as usize % PIXELS;
+        seed += 1;
+        if !pattern.points[k] {
+            pattern.toggle(k, &kernel);
+            placed += 1;
+        }
+    }
+
+    // spread the points out evenly by moving the most clustered one into the largest void, until
+    // that's where it already was.
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster, &kernel);
+        let void = pattern.largest_void();
+        pattern.toggle(void, &kernel);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; PIXELS];
+
+    let mut removing = pattern.clone();
+    for rank in (0..initial_points).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.toggle(cluster, &kernel);
+        ranks[cluster] = rank;
+    }
+
+    for rank in initial_points..PIXELS {
+        let void = pattern.largest_void();
+        pattern.toggle(void, &kernel);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / PIXELS as f32)
+        .collect()
+}
@@ -0,0 +1,71 @@
+use super::{
+    hash_to_float, mix_bits, permutation_element, SampleState, Sampler, ONE_MINUS_EPSILON,
+};
+
+/// The bases of the Halton sequence's dimensions.  Dimensions beyond these fall back to random
+/// values.
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Takes each pixel's samples from the Halton sequence, whose `n`th dimension is the radical
+/// inverse of the sample's index in the `n`th prime base.  The digits are randomly permuted,
+/// differently for each pixel, so that pixels don't share the same pattern.
+#[derive(Debug, Default, Clone)]
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.state.dimension as usize;
+        let h = self.state.next_hash();
+        let index = u64::from(self.state.index);
+
+        match PRIMES.get(dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(base, index, h as u32),
+            None => hash_to_float(mix_bits(h ^ index)),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Reverses the digits of `a` in `base` about the decimal point, permuting each digit by a hash
+/// of `hash` and the digits before it.
+fn owen_scrambled_radical_inverse(base: u32, mut a: u64, hash: u32) -> f32 {
+    let inv_base = f64::from(base).recip();
+    let mut inv_base_m = 1.0;
+    let mut reversed = 0u64;
+
+    // a digit past the precision of an f32 makes no difference.
+    while f64::from(base - 1) * inv_base_m > f64::from(f32::EPSILON) * 0.5 {
+        let next = a / u64::from(base);
+        let digit = (a - next * u64::from(base)) as u32;
+        let digit_hash = mix_bits(u64::from(hash) ^ reversed) as u32;
+        let digit = permutation_element(digit, base, digit_hash);
+
+        reversed = reversed * u64::from(base) + u64::from(digit);
+        inv_base_m *= inv_base;
+        a = next;
+    }
+
+    ((inv_base_m * reversed as f64) as f32).min(ONE_MINUS_EPSILON)
+}
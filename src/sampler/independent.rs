@@ -0,0 +1,36 @@
+use super::{hash, hash_to_float, mix_bits, Sampler};
+
+/// Draws every value independently at random, from a stream of pseudo-random numbers started
+/// afresh for each sample of each pixel.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    state: u64,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: 0 }
+    }
+
+    /// The next number in the stream, from Steele et al., "Fast Splittable Pseudorandom Number
+    /// Generators" (2014).
+    fn next(&mut self) -> f32 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        hash_to_float(mix_bits(self.state))
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32) {
+        self.state = hash(&[self.seed, i, j, u64::from(index)]);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.next(), self.next())
+    }
+}
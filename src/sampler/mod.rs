@@ -0,0 +1,168 @@
+mod blue_noise;
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use blue_noise::*;
+pub use halton::*;
+pub use independent::*;
+pub use sobol::*;
+pub use stratified::*;
+
+/// The largest `f32` below 1, which samples are clamped to so that they stay in `[0, 1)`.
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+/// A source of values in `[0, 1)` for the random choices made while rendering a pixel.
+///
+/// Each sample of a pixel draws its values from a sequence of dimensions: the first value
+/// requested after [`Sampler::start_pixel_sample`] comes from the first dimension, the next from
+/// the second, and so on.  Samplers that spread the values of each dimension evenly over a
+/// pixel's samples make images converge with fewer samples than independent random numbers do.
+pub trait Sampler {
+    /// Starts the `index`th sample of the pixel at (i, j).
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32);
+
+    /// Returns the value of the next dimension.
+    fn get_1d(&mut self) -> f32;
+
+    /// Returns the values of the next two dimensions, which are well distributed together as
+    /// well as on their own.
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+/// Which [`Sampler`] a camera renders with.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum SamplerKind {
+    /// Independent uniform random numbers.
+    #[default]
+    Independent,
+    /// Jittered samples, one in each of a grid of strata per pixel.
+    Stratified,
+    /// The Halton sequence, with its digits scrambled differently for each pixel.
+    Halton,
+    /// The Sobol sequence, Owen scrambled differently for each pixel.
+    Sobol,
+    /// The Sobol sequence, scrambled the same way for every pixel but shifted by a blue noise
+    /// mask, so that the error left in the image looks like fine, even grain.
+    BlueNoise,
+}
+
+impl SamplerKind {
+    /// Creates a sampler for rendering pixels with `samples_per_pixel` samples each.  Samplers
+    /// created with the same `seed` give the same values for the same sample of the same pixel.
+    pub(crate) fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+            Self::BlueNoise => Box::new(BlueNoiseSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+/// Keeps track of which sample of which pixel a sampler is on, and of the next dimension.
+#[derive(Debug, Default, Copy, Clone)]
+struct SampleState {
+    /// Mixed into every hash, so that different seeds give different samples.
+    seed: u64,
+    pixel: (u64, u64),
+    index: u32,
+    dimension: u64,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    fn start(&mut self, i: u64, j: u64, index: u32) {
+        self.pixel = (i, j);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Moves on to the next dimension, returning the one to sample from.
+    fn next_dimension(&mut self) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        dimension
+    }
+
+    /// A hash of the current pixel and the next dimension, moving on to the dimension after it.
+    fn next_hash(&mut self) -> u64 {
+        let dimension = self.next_dimension();
+        hash(&[self.seed, self.pixel.0, self.pixel.1, dimension])
+    }
+}
+
+/// Scrambles the bits of `v`, so that nearby inputs give unrelated outputs.
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e37_79b9_7f4a_7c15, |h, &v| mix_bits(h ^ v))
+}
+
+/// Turns a hash into a uniformly distributed value in `[0, 1)`.
+fn hash_to_float(h: u64) -> f32 {
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Where the `index`th sample of a pixel falls in a random order of its samples, chosen by `p`.
+/// Samples are shuffled within each block of `samples_per_pixel`, so that renders taking more
+/// samples than that, such as those with a time limit, go on to new points rather than reusing
+/// the first block's.
+fn shuffled_index(index: u32, samples_per_pixel: u32, p: u32) -> u32 {
+    let block = index / samples_per_pixel;
+    block * samples_per_pixel + permutation_element(index % samples_per_pixel, samples_per_pixel, p)
+}
+
+/// The `i`th element of a random permutation of `0..l`, chosen by `p`.  From Kensler,
+/// "Correlated Multi-Jittered Sampling" (2013).
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | (p >> 27));
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % l
+}
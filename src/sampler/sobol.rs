@@ -0,0 +1,81 @@
+use super::{mix_bits, shuffled_index, SampleState, Sampler, ONE_MINUS_EPSILON};
+
+/// Takes each pixel's samples from the first two dimensions of the Sobol sequence, which are
+/// well distributed both alone and together, and reuses them for every dimension.  Each
+/// dimension shuffles the order of the samples and Owen scrambles their values differently, so
+/// that the dimensions don't correlate with each other.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let h = self.state.next_hash();
+        let index = shuffled_index(self.state.index, self.samples_per_pixel, h as u32);
+        to_float(owen_scramble(sobol_sample(index, 0), (h >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let h = self.state.next_hash();
+        self.state.next_dimension();
+        let index = shuffled_index(self.state.index, self.samples_per_pixel, h as u32);
+        (
+            to_float(owen_scramble(sobol_sample(index, 0), (h >> 32) as u32)),
+            to_float(owen_scramble(sobol_sample(index, 1), mix_bits(h) as u32)),
+        )
+    }
+}
+
+/// The `index`th point of the first (`dimension` 0) or second (`dimension` 1) dimension of the
+/// Sobol sequence, as a fraction of 2³².
+pub(super) fn sobol_sample(index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+
+    // the second dimension's direction numbers follow from the primitive polynomial x + 1.
+    let mut v = 0;
+    let mut direction = 1 << 31;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 != 0 {
+            v ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    v
+}
+
+/// Owen scrambles the bits of `v`: each bit is flipped depending on the bits above it.  From
+/// Laine and Karras, "Stratified Sampling for Stochastic Transparency" (2011), as improved in
+/// pbrt.
+pub(super) fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+/// Turns a fraction of 2³² into an `f32` in `[0, 1)`.
+pub(super) fn to_float(v: u32) -> f32 {
+    (v as f32 / (1u64 << 32) as f32).min(ONE_MINUS_EPSILON)
+}
@@ -0,0 +1,65 @@
+use super::{
+    hash_to_float, mix_bits, permutation_element, SampleState, Sampler, ONE_MINUS_EPSILON,
+};
+
+/// Divides each dimension (or pair of dimensions) into as many strata as there are samples per
+/// pixel, and places each sample at a random point in a different stratum.  Which sample lands
+/// in which stratum is shuffled separately for each dimension.  Pixels taking more samples than
+/// that fill the strata again, shuffled differently each time.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    state: SampleState,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            state: SampleState::new(seed),
+        }
+    }
+
+    /// The stratum, of `strata`, that the `index`th sample falls in for the dimension hashed to
+    /// `h`.
+    fn shuffled_stratum(&self, index: u32, strata: u32, h: u64) -> u32 {
+        let block = index / self.samples_per_pixel;
+        let p = mix_bits(h ^ u64::from(block).rotate_left(32)) as u32;
+        permutation_element(index % self.samples_per_pixel, strata, p)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, i: u64, j: u64, index: u32) {
+        self.state.start(i, j, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let h = self.state.next_hash();
+        let index = self.state.index;
+
+        let stratum = self.shuffled_stratum(index, self.samples_per_pixel, h);
+        let jitter = hash_to_float(mix_bits(h ^ u64::from(index)));
+        ((stratum as f32 + jitter) / self.samples_per_pixel as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let h = self.state.next_hash();
+        self.state.next_dimension();
+        let index = self.state.index;
+
+        // the grid is as close to square as can be; if the samples don't fill it, the strata
+        // they leave empty are chosen at random.
+        let columns = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let rows = self.samples_per_pixel.div_ceil(columns);
+        let stratum = self.shuffled_stratum(index, columns * rows, h);
+
+        let jitter = mix_bits(h ^ u64::from(index));
+        let x = (stratum % columns) as f32 + hash_to_float(jitter);
+        let y = (stratum / columns) as f32 + hash_to_float(mix_bits(jitter));
+        (
+            (x / columns as f32).min(ONE_MINUS_EPSILON),
+            (y / rows as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
@@ -0,0 +1,251 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::{
+    light::DirectionalLight,
+    spectrum::{xyz_to_linear_srgb, RGB_WAVELENGTHS},
+    vec3::{Color, Vec3},
+};
+
+/// What rays that escape the scene see.
+#[derive(Debug, Default, Clone)]
+pub enum Background {
+    /// A white-to-blue gradient from the horizon to the zenith.
+    #[default]
+    Gradient,
+    /// The same color in every direction.
+    Solid(Color),
+    /// A physically based daylight sky.
+    Sky(PreethamSky),
+}
+
+impl Background {
+    /// The radiance arriving from `direction`.  The disk of the sun is only included for camera
+    /// rays, since the sun's light reaches the rest of the scene through its [`DirectionalLight`],
+    /// except after specular bounces, which add [`Background::sun_disk`] themselves.
+    pub fn radiance(&self, direction: &Vec3, camera_ray: bool) -> Color {
+        let unit_direction = direction.normalize();
+        match self {
+            Background::Gradient => {
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                let color_1 = Color::new(1.0, 1.0, 1.0);
+                let color_2 = Color::new(0.5, 0.7, 1.0);
+
+                (1.0 - a) * color_1 + a * color_2
+            }
+            Background::Solid(color) => *color,
+            Background::Sky(sky) => {
+                if camera_ray && sky.in_sun_disk(&unit_direction) {
+                    sky.sun_radiance()
+                } else {
+                    sky.radiance(&unit_direction)
+                }
+            }
+        }
+    }
+
+    /// How much more radiance arrives from `direction` with the disk of the sun than without,
+    /// or `None` if the sun doesn't lie that way.
+    pub fn sun_disk(&self, direction: &Vec3) -> Option<Color> {
+        let unit_direction = direction.normalize();
+        match self {
+            Background::Sky(sky) if sky.in_sun_disk(&unit_direction) => {
+                Some(sky.sun_radiance() - sky.radiance(&unit_direction))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Angular diameter of the sun, in degrees.
+const SUN_ANGULAR_DIAMETER: f32 = 0.53;
+
+/// Coefficients of the Perez sky luminance distribution.
+#[derive(Debug, Copy, Clone)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    /// Evaluates the distribution for a view direction whose zenith angle has cosine `cos_theta`,
+    /// at an angle `gamma` away from the sun.
+    fn eval(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(0.01)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// The analytic daylight sky model from Preetham, Shirley and Smits, "A Practical Analytic Model
+/// for Daylight" (1999), along with a matching sun.
+#[derive(Debug, Clone)]
+pub struct PreethamSky {
+    sun_direction: Vec3,
+    turbidity: f32,
+    intensity: f32,
+    sun_intensity: f32,
+    /// Perez distributions for luminance and the two chromaticity coordinates.
+    perez: [Perez; 3],
+    /// Luminance and chromaticity at the zenith.
+    zenith: [f32; 3],
+}
+
+impl PreethamSky {
+    /// Creates a sky with the sun `elevation` degrees above the horizon, at an `azimuth` measured
+    /// in degrees from `-z` towards `+x`.  `turbidity` describes how hazy the air is, from about
+    /// 2 on a very clear day to 10 on a hazy one.
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> Self {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = FRAC_PI_2 - elevation.clamp(0.0, FRAC_PI_2);
+
+        let perez_y = Perez {
+            a: 0.1787 * t - 1.4630,
+            b: -0.3554 * t + 0.4275,
+            c: -0.0227 * t + 5.3251,
+            d: 0.1206 * t - 2.5771,
+            e: -0.0670 * t + 0.3703,
+        };
+        let perez_x = Perez {
+            a: -0.0193 * t - 0.2592,
+            b: -0.0665 * t + 0.0008,
+            c: -0.0004 * t + 0.2125,
+            d: -0.0641 * t - 0.8989,
+            e: -0.0033 * t + 0.0452,
+        };
+        let perez_yy = Perez {
+            a: -0.0167 * t - 0.2608,
+            b: -0.0950 * t + 0.0092,
+            c: -0.0079 * t + 0.2102,
+            d: -0.0441 * t - 1.6537,
+            e: -0.0109 * t + 0.0529,
+        };
+
+        // zenith luminance (in kcd/m²) and chromaticity
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let theta = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let turbidity = [t * t, t, 1.0];
+            turbidity
+                .iter()
+                .zip(m.iter())
+                .map(|(t, row)| {
+                    t * row
+                        .iter()
+                        .zip(theta.iter())
+                        .map(|(a, b)| a * b)
+                        .sum::<f32>()
+                })
+                .sum::<f32>()
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        Self {
+            sun_direction,
+            turbidity: t,
+            intensity: 0.05,
+            sun_intensity: 6.0,
+            perez: [perez_y, perez_x, perez_yy],
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+        }
+    }
+
+    /// Scales the brightness of the sky.  The model works in kcd/m², so the default scale of
+    /// 0.05 brings a midday sky to roughly the brightness of the default gradient background.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sets the irradiance of the sun before it's attenuated by the atmosphere.  Defaults to 6.
+    pub fn with_sun_intensity(mut self, sun_intensity: f32) -> Self {
+        self.sun_intensity = sun_intensity;
+        self
+    }
+
+    /// Unit vector pointing towards the sun.
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    /// The radiance of the sky (excluding the sun) arriving from `direction`, a unit vector.
+    /// Directions below the horizon see the sky at the horizon.
+    pub fn radiance(&self, direction: &Vec3) -> Color {
+        let cos_theta = direction.y().max(0.0);
+        let gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        // each quantity is its value at the zenith, scaled by the Perez distribution relative to
+        // the zenith.
+        let theta_s = self.sun_direction.y().clamp(0.0, 1.0).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].eval(cos_theta, gamma) / self.perez[i].eval(1.0, theta_s)
+        });
+
+        if luminance <= 0.0 || y <= 0.0 {
+            return Color::default();
+        }
+
+        let xyz = Color::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        let rgb = xyz_to_linear_srgb(xyz) * self.intensity;
+        Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+    }
+
+    /// Light from the sun, attenuated by the atmosphere it passes through.
+    pub fn sun(&self) -> DirectionalLight {
+        DirectionalLight::new(-self.sun_direction, self.sun_irradiance())
+            .with_angular_diameter(SUN_ANGULAR_DIAMETER)
+    }
+
+    fn sun_irradiance(&self) -> Color {
+        if self.sun_direction.y() <= 0.0 {
+            return Color::default();
+        }
+
+        // relative optical air mass, from Kasten and Young (1989)
+        let zenith_angle = self.sun_direction.y().acos().to_degrees();
+        let air_mass =
+            (self.sun_direction.y() + 0.50572 * (96.07995 - zenith_angle).powf(-1.6364)).recip();
+
+        // Rayleigh scattering by air, and Ångström's formula for scattering by aerosols.
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: f32| {
+            let lambda_um = lambda * 1e-3;
+            let rayleigh = 0.008735 * lambda_um.powf(-4.08);
+            let aerosol = beta * lambda_um.powf(-1.3);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        };
+        let [r, g, b] = RGB_WAVELENGTHS;
+
+        Color::new(transmittance(r), transmittance(g), transmittance(b)) * self.sun_intensity
+    }
+
+    fn in_sun_disk(&self, direction: &Vec3) -> bool {
+        direction.dot(&self.sun_direction) >= (0.5 * SUN_ANGULAR_DIAMETER).to_radians().cos()
+    }
+
+    /// Radiance of the sun's disk, spreading its irradiance over the solid angle it covers.
+    fn sun_radiance(&self) -> Color {
+        let cos_max = (0.5 * SUN_ANGULAR_DIAMETER).to_radians().cos();
+        self.sun_irradiance() / (TAU * (1.0 - cos_max))
+    }
+}
@@ -0,0 +1,357 @@
+use std::ops::{Add, AddAssign, Div, Mul};
+
+use crate::{geometry::HitRecord, material::Material, ray::Ray, sampler::Sampler, vec3::Color};
+
+/// Shortest wavelength (in nanometers) sampled by the spectral renderer.
+pub const LAMBDA_MIN: f32 = 360.0;
+/// Longest wavelength (in nanometers) sampled by the spectral renderer.
+pub const LAMBDA_MAX: f32 = 830.0;
+
+/// Number of wavelengths carried along each path.
+pub const N_SPECTRUM_SAMPLES: usize = 4;
+
+/// Integral of the CIE 1931 `y` matching function over the visible range, used to normalize
+/// estimates so that a constant spectrum of 1 has a luminance of 1.
+const CIE_Y_INTEGRAL: f32 = 106.856_895;
+
+/// Wavelengths (in nanometers) that the RGB renderer treats its red, green and blue channels as,
+/// for materials whose behavior depends on wavelength.
+pub const RGB_WAVELENGTHS: [f32; 3] = [610.0, 550.0, 465.0];
+
+/// Values the path tracer can carry along a path: either plain RGB, or a spectrum sampled at a
+/// handful of wavelengths.
+pub trait Radiance:
+    Copy
+    + Default
+    + Add<Output = Self>
+    + AddAssign
+    + Mul<Output = Self>
+    + Mul<f32, Output = Self>
+    + Div<f32, Output = Self>
+{
+    /// Extra state needed to interpret a value, e.g. which wavelengths a spectrum was sampled at.
+    type Wavelengths;
+
+    /// Number of channels in a value.
+    const CHANNELS: usize;
+
+    /// Returns the value of a single channel.
+    fn channel(&self, index: usize) -> f32;
+
+    /// Converts a linear RGB value to this representation.
+    fn from_rgb(rgb: Color, lambda: &Self::Wavelengths) -> Self;
+
+    /// Evaluates `f` at the wavelength represented by each channel.
+    fn from_fn(lambda: &Self::Wavelengths, f: impl Fn(f32) -> f32) -> Self;
+
+    /// Applies `f` to every channel.
+    fn map(self, f: impl Fn(f32) -> f32) -> Self;
+
+    /// Returns the average over all channels.
+    fn average(&self) -> f32;
+
+    /// Scatters `ray` off `material`, returning the scattered ray and its attenuation.
+    fn scatter(
+        material: &dyn Material,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut Self::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Self)>;
+}
+
+impl Radiance for Color {
+    type Wavelengths = ();
+
+    const CHANNELS: usize = 3;
+
+    fn channel(&self, index: usize) -> f32 {
+        self[index]
+    }
+
+    fn from_rgb(rgb: Color, _lambda: &Self::Wavelengths) -> Self {
+        rgb
+    }
+
+    fn from_fn(_lambda: &Self::Wavelengths, f: impl Fn(f32) -> f32) -> Self {
+        let [r, g, b] = RGB_WAVELENGTHS;
+        Color::new(f(r), f(g), f(b))
+    }
+
+    fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Color::new(f(self.x()), f(self.y()), f(self.z()))
+    }
+
+    fn average(&self) -> f32 {
+        (self.x() + self.y() + self.z()) / 3.0
+    }
+
+    fn scatter(
+        material: &dyn Material,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        _lambda: &mut Self::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Self)> {
+        material.scatter(ray, hit_record, sampler)
+    }
+}
+
+/// The wavelengths a single path is traced at, using hero wavelength sampling: one wavelength is
+/// chosen uniformly at random, and the rest are spaced evenly across the visible range from it.
+#[derive(Debug, Copy, Clone)]
+pub struct SampledWavelengths {
+    lambda: [f32; N_SPECTRUM_SAMPLES],
+    pdf: [f32; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    /// Samples wavelengths for a path, given a uniform random number `u` in `[0, 1)`.
+    pub fn sample_visible(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let delta = range / N_SPECTRUM_SAMPLES as f32;
+
+        let mut lambda = [hero; N_SPECTRUM_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as f32 * delta;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+
+        Self {
+            lambda,
+            pdf: [range.recip(); N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    /// The hero wavelength, which always survives [`Self::terminate_secondary`].
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn get(&self, index: usize) -> f32 {
+        self.lambda[index]
+    }
+
+    /// Drops every wavelength except the hero wavelength.  Materials whose scattering direction
+    /// depends on wavelength (such as dispersive glass) call this, since the remaining
+    /// wavelengths would have taken a different path.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf.iter_mut().skip(1) {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= N_SPECTRUM_SAMPLES as f32;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+
+    /// Converts radiance sampled at these wavelengths to linear sRGB.
+    pub fn to_rgb(&self, spectrum: SampledSpectrum) -> Color {
+        let xyz: Color = (0..N_SPECTRUM_SAMPLES)
+            .filter(|&i| self.pdf[i] != 0.0)
+            .map(|i| cie_xyz(self.lambda[i]) * (spectrum.values[i] / self.pdf[i]))
+            .sum();
+
+        xyz_to_linear_srgb(xyz / (N_SPECTRUM_SAMPLES as f32 * CIE_Y_INTEGRAL))
+    }
+}
+
+/// A spectral distribution sampled at the wavelengths of a [`SampledWavelengths`].
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SampledSpectrum {
+    values: [f32; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn splat(value: f32) -> Self {
+        Self {
+            values: [value; N_SPECTRUM_SAMPLES],
+        }
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.values.iter_mut().zip(rhs.values) {
+            *a += b;
+        }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(mut self, rhs: Self) -> Self::Output {
+        for (a, b) in self.values.iter_mut().zip(rhs.values) {
+            *a *= b;
+        }
+        self
+    }
+}
+
+impl Mul<f32> for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(mut self, rhs: f32) -> Self::Output {
+        for value in self.values.iter_mut() {
+            *value *= rhs;
+        }
+        self
+    }
+}
+
+impl Div<f32> for SampledSpectrum {
+    type Output = Self;
+
+    fn div(mut self, rhs: f32) -> Self::Output {
+        for value in self.values.iter_mut() {
+            *value /= rhs;
+        }
+        self
+    }
+}
+
+impl Radiance for SampledSpectrum {
+    type Wavelengths = SampledWavelengths;
+
+    const CHANNELS: usize = N_SPECTRUM_SAMPLES;
+
+    fn channel(&self, index: usize) -> f32 {
+        self.values[index]
+    }
+
+    fn from_rgb(rgb: Color, lambda: &Self::Wavelengths) -> Self {
+        // Smits' method only covers reflectances, so scale brighter values down into range first.
+        let scale = rgb.x().max(rgb.y()).max(rgb.z()).max(1.0);
+        let rgb = rgb / scale;
+        Self::from_fn(lambda, |l| rgb_to_spectrum(rgb, l) * scale)
+    }
+
+    fn from_fn(lambda: &Self::Wavelengths, f: impl Fn(f32) -> f32) -> Self {
+        let mut values = [0.0; N_SPECTRUM_SAMPLES];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = f(lambda.get(i));
+        }
+        Self { values }
+    }
+
+    fn map(mut self, f: impl Fn(f32) -> f32) -> Self {
+        for value in self.values.iter_mut() {
+            *value = f(*value);
+        }
+        self
+    }
+
+    fn average(&self) -> f32 {
+        self.values.iter().sum::<f32>() / N_SPECTRUM_SAMPLES as f32
+    }
+
+    fn scatter(
+        material: &dyn Material,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        lambda: &mut Self::Wavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Self)> {
+        material.scatter_spectral(ray, hit_record, lambda, sampler)
+    }
+}
+
+/// Evaluates the CIE 1931 color matching functions at `lambda`, using the multi-lobe Gaussian fit
+/// from Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching
+/// Functions" (2013).
+pub fn cie_xyz(lambda: f32) -> Color {
+    fn g(x: f32, mu: f32, sigma_1: f32, sigma_2: f32) -> f32 {
+        let t = (x - mu) / if x < mu { sigma_1 } else { sigma_2 };
+        (-0.5 * t * t).exp()
+    }
+
+    let x = 1.056 * g(lambda, 599.8, 37.9, 31.0) + 0.362 * g(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * g(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(lambda, 568.8, 46.9, 40.5) + 0.286 * g(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(lambda, 437.0, 11.8, 36.0) + 0.681 * g(lambda, 459.0, 26.0, 13.8);
+
+    Color::new(x, y, z)
+}
+
+/// Converts CIE XYZ to linear sRGB, white balanced so that the equal-energy spectrum maps to
+/// `(1, 1, 1)`.
+pub fn xyz_to_linear_srgb(xyz: Color) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+
+    Color::new(r / 1.2048, g / 0.9484, b / 0.9087)
+}
+
+/// Upsamples an RGB reflectance to a spectrum and evaluates it at `lambda`, using Smits, "An RGB
+/// to Spectrum Conversion for Reflectances" (1999).
+pub fn rgb_to_spectrum(rgb: Color, lambda: f32) -> f32 {
+    // Smits' basis spectra, sampled in 10 bins spanning 380nm to 720nm.
+    const WHITE: [f32; 10] = [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ];
+    const CYAN: [f32; 10] = [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ];
+    const MAGENTA: [f32; 10] = [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ];
+    const YELLOW: [f32; 10] = [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ];
+    const RED: [f32; 10] = [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ];
+    const GREEN: [f32; 10] = [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+    ];
+    const BLUE: [f32; 10] = [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ];
+
+    let bin = (((lambda - 380.0) / 34.0).max(0.0) as usize).min(9);
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+
+    let value = if r <= g && r <= b {
+        r * WHITE[bin]
+            + if g <= b {
+                (g - r) * CYAN[bin] + (b - g) * BLUE[bin]
+            } else {
+                (b - r) * CYAN[bin] + (g - b) * GREEN[bin]
+            }
+    } else if g <= r && g <= b {
+        g * WHITE[bin]
+            + if r <= b {
+                (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin]
+            } else {
+                (b - g) * MAGENTA[bin] + (r - b) * RED[bin]
+            }
+    } else {
+        b * WHITE[bin]
+            + if r <= g {
+                (r - b) * YELLOW[bin] + (g - r) * GREEN[bin]
+            } else {
+                (g - b) * YELLOW[bin] + (r - g) * RED[bin]
+            }
+    };
+
+    value.max(0.0)
+}
@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+
 #[derive(Debug)]
 pub struct Range<Idx> {
     pub start: Idx,
@@ -47,3 +49,24 @@ where
         }
     }
 }
+
+/// Inserts `-suffix` between the stem and extension of `path`, e.g. `out.ppm` becomes
+/// `out-left.ppm`.
+pub(crate) fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}-{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{suffix}"),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Replaces the file at `path` with `contents` by writing them to a temporary file beside it and
+/// renaming that over it, so that anything reading the file sees either the old contents or the
+/// new ones, never a mix.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{file_name}.tmp"));
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)
+}
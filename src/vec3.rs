@@ -5,7 +5,7 @@ use std::{
     ops::{self, Add, AddAssign, Mul, MulAssign},
 };
 
-use rand::{random, thread_rng, Rng};
+use rand::{random, Rng};
 
 use crate::util::Range;
 
@@ -20,28 +20,33 @@ impl Vec3 {
         Self { data: [x, y, z] }
     }
 
-    pub fn random() -> Self {
-        Self::new(rand::random(), rand::random(), rand::random())
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new(rng.gen(), rng.gen(), rng.gen())
     }
 
-    pub fn random_in_range(min: f32, max: f32) -> Self {
-        let x = rand::thread_rng().gen_range(min..max);
-        let y = rand::thread_rng().gen_range(min..max);
-        let z = rand::thread_rng().gen_range(min..max);
+    pub fn random_in_range<R: Rng + ?Sized>(rng: &mut R, min: f32, max: f32) -> Self {
+        let x = rng.gen_range(min..max);
+        let y = rng.gen_range(min..max);
+        let z = rng.gen_range(min..max);
         Self::new(x, y, z)
     }
 
     pub fn random_on_unit_sphere() -> Self {
+        Self::sample_unit_sphere((random(), random()))
+    }
+
+    /// Maps a pair of uniform values `(u, v)` in `[0, 1)` to a point uniformly distributed on the
+    /// unit sphere.
+    pub fn sample_unit_sphere((u, v): (f32, f32)) -> Self {
         // See https://mathworld.wolfram.com/SpherePointPicking.html for why this works.
-        let theta = rand::thread_rng().gen_range(0.0..std::f32::consts::TAU);
-        let u: f32 = rand::thread_rng().gen_range(-1.0..1.0);
+        let theta = TAU * u;
+        let z = 1.0 - 2.0 * v;
 
         let (sin_theta, cos_theta) = theta.sin_cos();
-        let sin_phi = u.mul_add(-u, 1.0).sqrt();
+        let sin_phi = z.mul_add(-z, 1.0).max(0.0).sqrt();
 
         let x = cos_theta * sin_phi;
         let y = sin_theta * sin_phi;
-        let z = u;
 
         Self::new(x, y, z)
     }
@@ -56,8 +61,14 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disc() -> Self {
-        let r = random::<f32>().sqrt();
-        let theta = thread_rng().gen_range(0.0..TAU);
+        Self::sample_unit_disc((random(), random()))
+    }
+
+    /// Maps a pair of uniform values `(u, v)` in `[0, 1)` to a point uniformly distributed in the
+    /// unit disc on the xy plane.
+    pub fn sample_unit_disc((u, v): (f32, f32)) -> Self {
+        let r = u.sqrt();
+        let theta = TAU * v;
 
         let x = r * theta.cos();
         let y = r * theta.sin();
@@ -251,23 +262,33 @@ impl Color {
         writer: &mut W,
         samples_per_pixel: NonZeroU32,
     ) -> std::io::Result<()> {
+        let [r, g, b] = self.to_rgb8(samples_per_pixel);
+        writeln!(writer, "{} {} {}", r, g, b)
+    }
+
+    /// The 8 bit, sRGB encoded value of each channel of the average of `samples_per_pixel`
+    /// samples summing to this color, clipped to the range from black to white.
+    pub fn to_rgb8(&self, samples_per_pixel: NonZeroU32) -> [u8; 3] {
         let scale = (u32::from(samples_per_pixel) as f32).recip();
 
-        let r = linear_to_gamma(self.x() * scale);
-        let g = linear_to_gamma(self.y() * scale);
-        let b = linear_to_gamma(self.z() * scale);
-
-        static INTENSITY: crate::util::Range<f32> = Range::new(0.0, 0.999);
-        writeln!(
-            writer,
-            "{} {} {}",
-            (256.0 * INTENSITY.clamp(r)) as u8,
-            (256.0 * INTENSITY.clamp(g)) as u8,
-            (256.0 * INTENSITY.clamp(b)) as u8,
-        )
+        let r = linear_to_srgb(self.x() * scale);
+        let g = linear_to_srgb(self.y() * scale);
+        let b = linear_to_srgb(self.z() * scale);
+
+        static INTENSITY: crate::util::Range<f32> = Range::new(0.0, 1.0);
+        [
+            (255.0 * INTENSITY.clamp(r)).round() as u8,
+            (255.0 * INTENSITY.clamp(g)).round() as u8,
+            (255.0 * INTENSITY.clamp(b)).round() as u8,
+        ]
     }
 }
 
-fn linear_to_gamma(linear_component: f32) -> f32 {
-    linear_component.sqrt()
+/// The sRGB transfer function, which encodes a linear value from 0 to 1 for display.
+fn linear_to_srgb(linear_component: f32) -> f32 {
+    if linear_component <= 0.003_130_8 {
+        12.92 * linear_component
+    } else {
+        1.055 * linear_component.powf(2.4f32.recip()) - 0.055
+    }
 }